@@ -0,0 +1,289 @@
+use crate::connection::{Connection, Phase, CONNECTIONS};
+use crate::connection_handler::{set_intercept_enabled, write_bytes};
+use crate::materialization::{clear_divergences, export, persistence, table_key};
+use crate::mysql::command::MySqlCommand;
+use crate::mysql::packet::{ErrorData, OkData, Packet};
+use crate::mysql::text_result_set::TextResultSet;
+use crate::stats::{self, STATS};
+use sqlparser::ast::{Ident, ObjectName};
+use sqlparser::dialect::MySqlDialect;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::fs;
+
+/// Keyword introducing statements which are answered by the proxy itself instead of being forwarded upstream.
+const ADMIN_KEYWORD: &str = "MOCKSYSQL";
+
+/// ER_UNKNOWN_ERROR
+const ADMIN_ERROR_CODE: u16 = 1105;
+
+/// Outcome of an admin command.
+#[derive(Debug, PartialEq)]
+enum Response {
+    /// Sent as the info message of an OK packet.
+    Info(String),
+    ResultSet(TextResultSet),
+}
+
+/// Answers `MOCKSYSQL ...` statements locally, returning true if the last command was one of them.
+pub fn handle_command(connection: &mut Connection, packets: &[Packet]) -> bool {
+    if connection.phase != Phase::PendingResponse || packets.len() != 1 {
+        return false;
+    }
+
+    let words = match connection.get_last_command() {
+        Some(command) if command.com_code == MySqlCommand::ComQuery => {
+            match admin_statement(&command.arg) {
+                Some(words) => words,
+                None => return false,
+            }
+        }
+        _ => return false,
+    };
+
+    let client_flag = connection
+        .get_handshake_response()
+        .map(|handshake_response| handshake_response.client_flag)
+        .unwrap_or_default();
+    let sequence = packets.first().unwrap().header.seq + 1;
+
+    let response = match execute(connection, &words) {
+        Ok(Response::Info(info)) => vec![OkData {
+            header: 0x00,
+            affected_rows: 0,
+            last_insert_id: 0,
+            status_flags: None,
+            warnings: None,
+            info: Some(info),
+            session_state_info: None,
+        }
+        .to_packet(sequence, client_flag)],
+        Ok(Response::ResultSet(result_set)) => result_set.to_packets(sequence, client_flag),
+        Err(e) => {
+            vec![ErrorData::new(ADMIN_ERROR_CODE, "HY000", e).to_packet(sequence, client_flag)]
+        }
+    };
+
+    stats::increment(&STATS.admin_commands);
+    connection.phase = Phase::Command;
+    let bytes: Vec<u8> = response.iter().flat_map(Packet::to_bytes).collect();
+    write_bytes(&mut connection.client_connection, &bytes);
+    true
+}
+
+/// Splits an admin statement into its words following the `MOCKSYSQL` keyword, unquoting string literals.
+fn admin_statement(sql: &str) -> Option<Vec<String>> {
+    let tokens = Tokenizer::new(&MySqlDialect {}, sql).tokenize().ok()?;
+
+    let mut words = tokens.into_iter().filter_map(|token| match token {
+        Token::Word(word) => Some(word.value),
+        Token::SingleQuotedString(s) | Token::DoubleQuotedString(s) | Token::Number(s, _) => {
+            Some(s)
+        }
+        Token::Whitespace(_) | Token::SemiColon => None,
+        token => Some(token.to_string()),
+    });
+
+    match words.next() {
+        Some(keyword) if keyword.eq_ignore_ascii_case(ADMIN_KEYWORD) => Some(words.collect()),
+        _ => None,
+    }
+}
+
+fn execute(connection: &mut Connection, words: &[String]) -> Result<Response, String> {
+    let command = words.first().map(|word| word.to_uppercase());
+    let subject = words.get(1).map(|word| word.to_uppercase());
+    let argument = words.get(1).map(String::as_str);
+
+    match (command.as_deref(), subject.as_deref()) {
+        (Some("SNAPSHOT"), _) => {
+            let path = persistence::state_file(argument)?;
+            let count = persistence::snapshot(&connection.namespaces, &path)?;
+            Ok(Response::Info(format!(
+                "Saved {} divergences to {}",
+                count, path
+            )))
+        }
+        (Some("RESTORE"), _) => {
+            let path = persistence::state_file(argument)?;
+            let count = persistence::restore(&connection.namespaces, &connection.tables, &path)?;
+            Ok(Response::Info(format!(
+                "Restored {} divergences from {}",
+                count, path
+            )))
+        }
+        (Some("EXPORT"), _) => match argument {
+            Some(path) => {
+                let path = persistence::admin_file(path)?;
+                let script = export::export_script(&connection.diff);
+                fs::write(&path, script).map_err(|e| e.to_string())?;
+                Ok(Response::Info(format!("Exported diffs to {}", path)))
+            }
+            None => {
+                let mut result_set = TextResultSet::new(&["statement"]);
+                for statement in export::export_sql(&connection.diff) {
+                    result_set.push_row(vec![Some(statement)]);
+                }
+                Ok(Response::ResultSet(result_set))
+            }
+        },
+        (Some("SHOW"), Some("DIFFS")) => {
+            let table = table_argument(connection, &words[2..]);
+            let mut result_set = TextResultSet::new(&["sequence", "table", "statement"]);
+            for (sequence, table, statement) in
+                export::diff_statements(&connection.diff, table.as_deref())
+            {
+                result_set.push_row(vec![
+                    Some(sequence.to_string()),
+                    Some(table),
+                    Some(statement),
+                ]);
+            }
+            Ok(Response::ResultSet(result_set))
+        }
+        (Some("CLEAR"), Some("DIFFS")) => {
+            let table = table_argument(connection, &words[2..]);
+            // Divergences buffered by the ongoing transaction of the connection are discarded as well.
+            let count = clear_divergences(&connection.diff, table.as_deref())
+                + connection
+                    .transaction
+                    .pending_in(&connection.diff)
+                    .map_or(0, |pending| clear_divergences(pending, table.as_deref()));
+            Ok(Response::Info(format!("Cleared {} divergences", count)))
+        }
+        (Some("SHOW"), Some("CONNECTIONS")) => Ok(Response::ResultSet(connections())),
+        (Some("SET"), Some("INTERCEPT")) => {
+            let enabled = match words.last().map(|word| word.to_uppercase()).as_deref() {
+                Some("ON" | "1" | "TRUE") => true,
+                Some("OFF" | "0" | "FALSE") => false,
+                _ => return Err(format!("Usage: {} SET intercept = on|off", ADMIN_KEYWORD)),
+            };
+            set_intercept_enabled(enabled);
+            Ok(Response::Info(format!(
+                "Write interception {}",
+                if enabled { "enabled" } else { "disabled" }
+            )))
+        }
+        _ => Err(format!(
+            "Unknown {} command: {}",
+            ADMIN_KEYWORD,
+            words.join(" ")
+        )),
+    }
+}
+
+/// Table key named by the remaining words of a command, e.g, `shop`, `.`, `account`.
+fn table_argument(connection: &Connection, words: &[String]) -> Option<String> {
+    if words.is_empty() {
+        return None;
+    }
+
+    let name = ObjectName::from(
+        words
+            .concat()
+            .split('.')
+            .map(Ident::new)
+            .collect::<Vec<_>>(),
+    );
+    Some(table_key(&name, connection.current_database.as_deref()))
+}
+
+fn connections() -> TextResultSet {
+    let mut result_set = TextResultSet::new(&[
+        "id",
+        "host",
+        "user",
+        "db",
+        "namespace",
+        "phase",
+        "last_command",
+    ]);
+
+    let mut connections: Vec<_> = CONNECTIONS
+        .iter()
+        .map(|entry| entry.value().clone())
+        .collect();
+    connections.sort_by_key(|connection| connection.id);
+
+    for connection in connections {
+        result_set.push_row(vec![
+            Some(connection.id.to_string()),
+            connection.client_address,
+            connection.username,
+            connection.database,
+            Some(connection.namespace),
+            Some(format!("{:?}", connection.phase)),
+            connection.last_command,
+        ]);
+    }
+
+    result_set
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::admin::{admin_statement, execute, Response};
+    use crate::connection::Connection;
+    use crate::materialization::get_diff;
+    use crate::mysql::command::{Command, MySqlCommand};
+
+    #[test]
+    fn test_admin_statement() {
+        assert_eq!(
+            Some(vec![
+                "SNAPSHOT".to_string(),
+                "/tmp/state 1.json".to_string()
+            ]),
+            admin_statement("mocksysql SNAPSHOT '/tmp/state 1.json';")
+        );
+        assert_eq!(None, admin_statement("select 'MOCKSYSQL'"));
+    }
+
+    #[test]
+    fn test_diff_commands() {
+        let mut connection = Connection::default();
+        connection.current_database = Some("shop".to_string());
+
+        for query in [
+            "update account set status = 'A' where id = 1",
+            "delete from orders",
+        ] {
+            get_diff(
+                &mut connection.diff,
+                &connection.tables,
+                Some("shop"),
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        let mut execute = |statement: &str| {
+            execute(&mut connection, &admin_statement(statement).unwrap()).unwrap()
+        };
+
+        match execute("MOCKSYSQL SHOW DIFFS") {
+            Response::ResultSet(result_set) => {
+                assert_eq!(vec!["sequence", "table", "statement"], result_set.columns);
+                assert_eq!(2, result_set.rows.len());
+                assert_eq!(Some("shop.account".to_string()), result_set.rows[0][1]);
+                assert_eq!(
+                    Some("DELETE FROM `shop`.`orders`;".to_string()),
+                    result_set.rows[1][2]
+                );
+            }
+            response => panic!("Unexpected response {:?}", response),
+        }
+
+        assert_eq!(
+            Response::Info("Cleared 1 divergences".to_string()),
+            execute("MOCKSYSQL CLEAR DIFFS `shop`.account")
+        );
+        match execute("MOCKSYSQL SHOW DIFFS orders") {
+            Response::ResultSet(result_set) => assert_eq!(1, result_set.rows.len()),
+            response => panic!("Unexpected response {:?}", response),
+        }
+        assert_eq!(
+            Response::Info("Cleared 1 divergences".to_string()),
+            execute("MOCKSYSQL CLEAR DIFFS")
+        );
+        assert!(connection.diff.is_empty());
+    }
+}
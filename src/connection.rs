@@ -1,29 +1,49 @@
-#[cfg(feature = "replay")]
-use crate::materialization::ReplayLog;
-use crate::materialization::StateDiffLog;
+use crate::materialization::namespace::{diff_log, Namespaces, DEFAULT_NAMESPACE};
+use crate::materialization::transaction::Transaction;
+use crate::materialization::{StateDiffLog, TableStore};
 use crate::mysql::accumulator::handshake::HandshakeAccumulator;
 use crate::mysql::accumulator::handshake_response::HandshakeResponseAccumulator;
 use crate::mysql::accumulator::result_set::ResponseAccumulator;
 use crate::mysql::command::Command;
 #[cfg(feature = "replay")]
-use kafka::producer::Producer;
+use crate::replay::ReplayLog;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
 #[cfg(feature = "tls")]
 use rustls::{ClientConnection, ServerConnection, StreamOwned};
-#[cfg(feature = "replay")]
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use std::cell::RefCell;
-use std::net::TcpStream;
-#[cfg(feature = "replay")]
-use std::sync::{Arc, Mutex};
-
 #[cfg(feature = "replay")]
-pub type KafkaProducerConfig = Option<(String, Arc<Mutex<Producer>>)>;
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Open connections by id, kept up to date by [`Connection::publish`].
+pub static CONNECTIONS: Lazy<DashMap<u64, ConnectionInfo>> = Lazy::new(DashMap::new);
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Summary of an open connection, as listed by admin commands and the control API.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub client_address: Option<String>,
+    pub username: Option<String>,
+    pub database: Option<String>,
+    pub namespace: String,
+    pub phase: Phase,
+    pub last_command: Option<String>,
+}
 
 #[allow(dead_code)]
 pub struct Connection {
+    pub id: u64,
+    pub client_address: Option<String>,
     pub phase: Phase,
     pub partial_bytes: Option<Vec<u8>>,
     pub last_command: Option<Command>,
+    /// Database selected by the client, through the handshake, COM_INIT_DB or a `USE` statement.
+    pub current_database: Option<String>,
 
     pub handshake: Option<HandshakeAccumulator>,
     pub handshake_response: Option<HandshakeResponseAccumulator>,
@@ -32,40 +52,62 @@ pub struct Connection {
     pub server_connection: SwitchableConnection,
 
     query_response: ResponseAccumulator,
+    /// Diff log of the namespace selected for the current command.
     pub diff: StateDiffLog,
+    pub namespaces: Namespaces,
+    /// Namespace of the connection, derived from the handshake response.
+    pub namespace: String,
+    pub transaction: Transaction,
+    pub tables: TableStore,
 
+    /// Store responses are served from, if replaying is enabled.
     #[cfg(feature = "replay")]
     pub replay: ReplayLog,
+    /// Replayed response to the last command, yet to be sent to the client.
     #[cfg(feature = "replay")]
     pub partial_replay_bytes: Option<Vec<u8>>,
+    /// Number of responses replayed to the connection by replay key.
     #[cfg(feature = "replay")]
-    pub kafka_producer_config: KafkaProducerConfig,
+    pub replay_positions: HashMap<String, usize>,
+    /// Store responses are recorded to, if recording is enabled.
+    #[cfg(feature = "replay")]
+    pub recorder: ReplayLog,
 }
 
 impl Connection {
     pub fn new(
         server: SwitchableConnection,
         client: SwitchableConnection,
-        state_difference_map: StateDiffLog,
-        #[cfg(feature = "replay")] replay_map: ReplayLog,
-        #[cfg(feature = "replay")] kafka_config: KafkaProducerConfig,
+        namespaces: Namespaces,
+        tables: TableStore,
+        #[cfg(feature = "replay")] replay: ReplayLog,
+        #[cfg(feature = "replay")] recorder: ReplayLog,
     ) -> Connection {
         Connection {
+            id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            client_address: client.peer_addr().map(|address| address.to_string()),
             client_connection: client,
             server_connection: server,
             phase: Phase::Handshake,
             partial_bytes: None,
             last_command: None,
+            current_database: None,
             handshake: None,
             handshake_response: None,
             query_response: ResponseAccumulator::default(),
-            diff: state_difference_map,
+            diff: diff_log(&namespaces, DEFAULT_NAMESPACE),
+            namespaces,
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            transaction: Transaction::default(),
+            tables,
             #[cfg(feature = "replay")]
-            replay: replay_map,
+            replay,
             #[cfg(feature = "replay")]
-            kafka_producer_config: kafka_config,
+            recorder,
             #[cfg(feature = "replay")]
             partial_replay_bytes: None,
+            #[cfg(feature = "replay")]
+            replay_positions: HashMap::new(),
         }
     }
 
@@ -74,9 +116,10 @@ impl Connection {
         Connection::new(
             SwitchableConnection::None,
             SwitchableConnection::None,
-            StateDiffLog::default(),
+            Namespaces::default(),
+            TableStore::default(),
             #[cfg(feature = "replay")]
-            ReplayLog::default(),
+            None,
             #[cfg(feature = "replay")]
             None,
         )
@@ -94,6 +137,17 @@ impl Connection {
         self.handshake_response.as_ref()
     }
 
+    /// Capabilities in effect on the upstream connection, i.e, those requested by the client and supported by the
+    /// server.
+    pub fn negotiated_flags(&self) -> Option<u32> {
+        let client_flag = self.get_handshake_response()?.client_flag;
+
+        Some(match &self.handshake {
+            Some(handshake) => client_flag & handshake.capability_flags(),
+            None => client_flag,
+        })
+    }
+
     pub fn get_response_accumulator(&self) -> ResponseAccumulator {
         self.query_response.clone()
     }
@@ -101,6 +155,27 @@ impl Connection {
     pub fn set_response_accumulator(&mut self, accumulator: ResponseAccumulator) {
         self.query_response = accumulator;
     }
+
+    /// Updates the entry of the connection in [`CONNECTIONS`].
+    pub fn publish(&self) {
+        CONNECTIONS.insert(
+            self.id,
+            ConnectionInfo {
+                id: self.id,
+                client_address: self.client_address.clone(),
+                username: self
+                    .get_handshake_response()
+                    .map(|handshake_response| handshake_response.username().to_string()),
+                database: self.current_database.clone(),
+                namespace: self.namespace.clone(),
+                phase: self.phase.clone(),
+                last_command: self
+                    .last_command
+                    .as_ref()
+                    .map(|command| command.arg.clone()),
+            },
+        );
+    }
 }
 
 impl Connection {
@@ -115,7 +190,7 @@ impl Connection {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 #[repr(u8)]
 #[derive(Hash)]
 pub enum Phase {
@@ -138,10 +213,24 @@ pub enum SwitchableConnection {
     ClientTls(RefCell<StreamOwned<ServerConnection, TcpStream>>),
     #[cfg(feature = "tls")]
     ServerTls(RefCell<StreamOwned<ClientConnection, TcpStream>>),
-    #[cfg(test)]
+    /// No server, when replaying offline.
+    #[cfg(any(test, feature = "replay"))]
     None,
 }
 impl SwitchableConnection {
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        match self {
+            SwitchableConnection::Plain(stream) => stream.borrow().peer_addr().ok(),
+            #[cfg(feature = "tls")]
+            SwitchableConnection::ClientTls(stream) => stream.borrow().sock.peer_addr().ok(),
+            #[cfg(feature = "tls")]
+            SwitchableConnection::ServerTls(stream) => stream.borrow().sock.peer_addr().ok(),
+            #[cfg(any(test, feature = "replay"))]
+            SwitchableConnection::None => None,
+        }
+    }
+
+    #[cfg(feature = "tls")]
     pub fn take(self) -> TcpStream {
         match self {
             SwitchableConnection::Plain(stream) => stream.into_inner(),
@@ -149,9 +238,3 @@ impl SwitchableConnection {
         }
     }
 }
-#[cfg(feature = "replay")]
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ReplayLogEntry {
-    pub last_command: String,
-    pub output: String,
-}
@@ -1,21 +1,28 @@
+use crate::connection::{Phase, SwitchableConnection, CONNECTIONS};
+use crate::materialization::namespace::Namespaces;
 #[cfg(feature = "replay")]
-use crate::connection::KafkaProducerConfig;
-#[cfg(feature = "replay")]
-use crate::connection::ReplayLogEntry;
-use crate::connection::{Phase, SwitchableConnection};
-#[cfg(feature = "replay")]
-use crate::materialization::ReplayLog;
-use crate::materialization::StateDiffLog;
+use crate::materialization::transaction::Transaction;
+use crate::materialization::{AffectedRows, InsertedRows, TableStore};
+use crate::mysql::accumulator::CapabilityFlags;
 use crate::mysql::command::Command;
+#[cfg(feature = "replay")]
+use crate::mysql::command::MySqlCommand;
 use crate::mysql::command::MySqlCommand::ComQuery;
-use crate::mysql::packet::{OkData, Packet, PacketType};
-#[cfg(feature = "tls")]
-use crate::tls::{handle_client_tls, handle_server_tls};
-use crate::{connection::Connection, materialization, state_handler};
+use crate::mysql::packet::{ErrorData, OkData, Packet, PacketType};
+#[cfg(feature = "replay")]
+use crate::replay::fingerprint::replay_key;
+#[cfg(feature = "replay")]
+use crate::replay::miss::{self, MissPolicy};
+#[cfg(feature = "replay")]
+use crate::replay::session::{self, SessionEvent, OFFLINE_SESSION};
 #[cfg(feature = "replay")]
-use base64::Engine;
+use crate::replay::{self, response, sequence};
 #[cfg(feature = "replay")]
-use kafka::producer::Record;
+use crate::replay::{ReplayLog, ReplayLogEntry};
+use crate::stats::{self, STATS};
+#[cfg(feature = "tls")]
+use crate::tls::{handle_client_tls, handle_server_tls};
+use crate::{admin, connection::Connection, materialization, state_handler};
 use log::{debug, error};
 use once_cell::sync::Lazy;
 #[cfg(feature = "tls")]
@@ -23,10 +30,13 @@ use rustls::StreamOwned;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
-use std::sync::atomic::AtomicU8;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::LazyLock;
+use std::sync::RwLock;
 use std::thread::sleep;
 use std::time::Duration;
+#[cfg(feature = "replay")]
+use std::time::Instant;
 use std::{
     env,
     io::{Error, Read, Write},
@@ -34,7 +44,6 @@ use std::{
     thread,
 };
 
-static GLOBAL_COUNTER: AtomicU8 = AtomicU8::new(100);
 static SERVER_TRANSITION_PHASES: LazyLock<HashSet<Phase>> = LazyLock::new(|| {
     HashSet::from([
         Phase::HandshakeResponse,
@@ -43,13 +52,30 @@ static SERVER_TRANSITION_PHASES: LazyLock<HashSet<Phase>> = LazyLock::new(|| {
     ])
 });
 
-static INTERCEPT_WRITES: Lazy<String> =
-    Lazy::new(|| env::var("INTERCEPT_WRITES").unwrap_or_else(|_| "false".to_string()));
+/// Whether writes are intercepted, initialised from `INTERCEPT_WRITES` and toggled at runtime by admin commands.
+static INTERCEPT_WRITES: Lazy<AtomicBool> =
+    Lazy::new(|| AtomicBool::new(env::var("INTERCEPT_WRITES").is_ok_and(|val| val == "true")));
+
+/// Latency in milliseconds added before forwarding commands, by their first keyword, initialised from `DELAY_*`
+/// variables and adjustable at runtime through the control API.
+static DELAYS: Lazy<RwLock<HashMap<String, u64>>> = Lazy::new(|| {
+    let delays = env::vars()
+        .filter_map(|(key, value)| {
+            let command = key.strip_prefix("DELAY_")?;
+            match u64::from_str(&value) {
+                Ok(delay) => Some((command.to_uppercase(), delay)),
+                Err(_) => {
+                    error!(
+                        "Ignoring {}, {} is not a number of milliseconds",
+                        key, value
+                    );
+                    None
+                }
+            }
+        })
+        .collect();
 
-static DELAY_VARS: Lazy<HashMap<String, String>> = Lazy::new(|| {
-    env::vars()
-        .filter(|(k, _)| k.starts_with("DELAY_"))
-        .collect()
+    RwLock::new(delays)
 });
 
 static CLIENT_TRANSITION_PHASES: LazyLock<HashSet<Phase>> =
@@ -57,27 +83,49 @@ static CLIENT_TRANSITION_PHASES: LazyLock<HashSet<Phase>> =
 
 pub fn initiate(
     client: TcpStream,
-    state_difference_map: StateDiffLog,
-    #[cfg(feature = "replay")] kafka_config: KafkaProducerConfig,
-    #[cfg(feature = "replay")] replay_map: ReplayLog,
+    namespaces: Namespaces,
+    tables: TableStore,
+    #[cfg(feature = "replay")] recorder: ReplayLog,
+    #[cfg(feature = "replay")] replay: ReplayLog,
 ) {
     let target_address = env::var("TARGET_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3307".to_owned());
 
-    let server = TcpStream::connect(target_address).expect("Fault");
-
-    let connection = Connection::new(
-        SwitchableConnection::Plain(RefCell::new(server)),
+    #[cfg(feature = "replay")]
+    let server = match replay::offline_enabled() {
+        true => SwitchableConnection::None,
+        false => SwitchableConnection::Plain(RefCell::new(
+            TcpStream::connect(target_address).expect("Fault"),
+        )),
+    };
+    #[cfg(not(feature = "replay"))]
+    let server = SwitchableConnection::Plain(RefCell::new(
+        TcpStream::connect(target_address).expect("Fault"),
+    ));
+
+    #[allow(unused_mut)]
+    let mut connection = Connection::new(
+        server,
         SwitchableConnection::Plain(RefCell::new(client)),
-        state_difference_map,
+        namespaces,
+        tables,
         #[cfg(feature = "replay")]
-        replay_map,
+        replay,
         #[cfg(feature = "replay")]
-        kafka_config,
+        recorder,
     );
 
+    let id = connection.id;
+    stats::increment(&STATS.connections);
+
+    #[cfg(feature = "replay")]
+    if let Some(session) = OFFLINE_SESSION.get() {
+        connection.partial_replay_bytes = Some(response::frame(vec![session.greeting(id)], 0));
+    }
+
     let worker = thread::spawn(move || exchange(connection));
 
     worker.join().ok();
+    CONNECTIONS.remove(&id);
 }
 
 fn exchange(mut connection: Connection) -> Result<(), Error> {
@@ -89,7 +137,7 @@ fn exchange(mut connection: Connection) -> Result<(), Error> {
         // Server Loop
 
         #[cfg(feature = "replay")]
-        let mut kafka_log_buffer: Vec<u8> = Vec::with_capacity(4096);
+        let mut replay_log_buffer: Vec<u8> = Vec::with_capacity(4096);
 
         loop {
             debug!("Listening from server");
@@ -103,25 +151,33 @@ fn exchange(mut connection: Connection) -> Result<(), Error> {
                 bytes_count = Some(read_bytes(&mut connection.server_connection, &mut buf)?);
             }
 
-            #[cfg(not(feature = "replay"))]
-            let bytes_count = Some(read_bytes(&mut connection.server_connection, &mut buf)?);
-
+            #[cfg(feature = "replay")]
             let bytes_count = bytes_count.unwrap_or(0);
 
+            #[cfg(not(feature = "replay"))]
+            let bytes_count = read_bytes(&mut connection.server_connection, &mut buf)?;
+
             debug!("From server: {:?}", &buf[0..bytes_count].to_vec());
 
             if bytes_count == 0 {
                 return Ok(());
             }
 
+            #[cfg(feature = "replay")]
+            let phase = connection.phase.clone();
+
             packets = state_handler::process_incoming_frame(&buf, &mut connection, bytes_count);
+            connection.publish();
+
+            #[cfg(feature = "replay")]
+            record_session_packets(&connection, &phase, &packets, true);
 
             let encoded_bytes = state_handler::generate_outgoing_frame(&packets);
 
             write_bytes(&mut connection.client_connection, encoded_bytes.as_slice());
 
             #[cfg(feature = "replay")]
-            push_to_kafka_if_logging_enabled(&connection, &encoded_bytes, &mut kafka_log_buffer);
+            record_response_if_enabled(&connection, &encoded_bytes, &mut replay_log_buffer);
 
             if SERVER_TRANSITION_PHASES.contains(&connection.phase) {
                 debug!("Transitioning to client");
@@ -146,24 +202,54 @@ fn exchange(mut connection: Connection) -> Result<(), Error> {
                 return Ok(());
             }
 
+            #[cfg(feature = "replay")]
+            let phase = connection.phase.clone();
+
             packets = state_handler::process_incoming_frame(&buf, &mut connection, read_bytes);
+            connection.publish();
+
+            #[cfg(feature = "replay")]
+            record_session_packets(&connection, &phase, &packets, false);
 
             let encoded_bytes = state_handler::generate_outgoing_frame(&packets);
 
-            if !DELAY_VARS.is_empty() {
-                delay_if_required(&connection.last_command, &DELAY_VARS);
+            delay_if_required(&connection.last_command);
+
+            if admin::handle_command(&mut connection, &packets) {
+                continue;
+            }
+
+            if answer_local_query(&mut connection, &packets) {
+                continue;
             }
 
-            if intercept_enabled() && intercept_command(&mut connection, &packets) {
+            let writes_local_table = connection.last_command.as_ref().is_some_and(|command| {
+                materialization::local::writes_local_table(&connection, &command.ast)
+            });
+            if (intercept_enabled() || writes_local_table)
+                && intercept_command(&mut connection, &packets)
+            {
                 // Connection returns to command phase if the query is intercepted, so the client loop needs to be started again.
                 continue;
             }
 
-            // Don't send data to the server if replay is enabled and the client
+            // Queries answered from the replay store are not sent to the server.
             #[cfg(feature = "replay")]
-            // has performed a query.
-            if send_command_to_server(&connection) {
-                write_bytes(&mut connection.server_connection, encoded_bytes.as_slice());
+            match replay_response(&mut connection, &packets) {
+                Replay::Forward => {
+                    write_bytes(&mut connection.server_connection, encoded_bytes.as_slice())
+                }
+                Replay::Response(bytes) => connection.partial_replay_bytes = Some(bytes),
+                Replay::Error(packet) => {
+                    connection.phase = Phase::Command;
+                    write_bytes(&mut connection.client_connection, &packet.to_bytes());
+                    continue;
+                }
+                Replay::Fail => {
+                    return Err(Error::other(
+                        "No recorded response, closing the client connection",
+                    ))
+                }
             }
             #[cfg(not(feature = "replay"))]
             write_bytes(&mut connection.server_connection, encoded_bytes.as_slice());
@@ -216,104 +302,255 @@ fn send_command_to_server(connection: &Connection) -> bool {
 }
 
 #[cfg(feature = "replay")]
-fn push_to_kafka_if_logging_enabled(
+fn record_response_if_enabled(
     connection: &Connection,
-    encoded_bytes: &Vec<u8>,
-    kafka_log_buffer: &mut Vec<u8>,
+    encoded_bytes: &[u8],
+    replay_log_buffer: &mut Vec<u8>,
 ) {
+    if connection.recorder.is_none() && !session::recording() {
+        return;
+    }
+
     if let Some(command) = &connection.last_command {
-        if let Some((topic, producer)) = &connection.kafka_producer_config {
-            if let Some(_) = &connection.partial_bytes {
-                kafka_log_buffer.extend_from_slice(encoded_bytes);
-            } else {
-                let mut combined = Vec::with_capacity(kafka_log_buffer.len() + encoded_bytes.len());
-                combined.extend_from_slice(kafka_log_buffer);
-                combined.extend_from_slice(encoded_bytes);
-                kafka_log_buffer.clear();
-
-                let payload = serde_json::to_string(&ReplayLogEntry {
-                    last_command: command.arg.clone(),
-                    output: base64::engine::general_purpose::STANDARD.encode(combined),
-                });
-
-                if let Ok(payload) = payload {
-                    let mut handle = producer.lock().unwrap();
-                    let _ = handle.send(&Record::from_value(topic, payload.as_bytes().to_vec()));
+        if connection.partial_bytes.is_some() {
+            replay_log_buffer.extend_from_slice(encoded_bytes);
+        } else {
+            let mut combined = Vec::with_capacity(replay_log_buffer.len() + encoded_bytes.len());
+            combined.extend_from_slice(replay_log_buffer);
+            combined.extend_from_slice(encoded_bytes);
+            replay_log_buffer.clear();
+
+            let client_flag = connection
+                .get_handshake_response()
+                .map(|handshake_response| handshake_response.client_flag)
+                .unwrap_or_default();
+
+            let entry = ReplayLogEntry {
+                last_command: command.arg.clone(),
+                response: response::decode(&response::split_frames(&combined), client_flag),
+            };
+
+            session::record(SessionEvent::Command {
+                connection: connection.id,
+                entry: entry.clone(),
+            });
+
+            if let Some(recorder) = &connection.recorder {
+                if let Err(e) = recorder.record(entry) {
+                    error!("Unable to record response, {}", e);
                 }
             }
         }
     }
 }
 
+/// Records the greeting and authentication packets of the connection to the session recording.
+#[cfg(feature = "replay")]
+fn record_session_packets(
+    connection: &Connection,
+    phase: &Phase,
+    packets: &[Packet],
+    from_server: bool,
+) {
+    let greeting = match phase {
+        Phase::Handshake => true,
+        Phase::HandshakeResponse
+        | Phase::AuthInit
+        | Phase::AuthSwitchResponse
+        | Phase::AuthComplete => false,
+        _ => return,
+    };
+
+    if !session::recording() {
+        return;
+    }
+
+    for packet in packets {
+        session::record(SessionEvent::packet(
+            connection.id,
+            from_server,
+            greeting,
+            &packet.body,
+        ));
+    }
+}
+
+/// Serves the next chunk of the replayed response to the last command, if any.
 #[cfg(feature = "replay")]
 fn get_response_from_cache_if_replay_enabled(
     connection: &mut Connection,
     buf: &mut [u8],
 ) -> Option<usize> {
-    if !send_command_to_server(&connection) {
-        if let Some(replay_logs) = &connection.replay {
-            loop {
-                {
-                    let replay_logs = replay_logs.lock().unwrap();
-                    let last_command = &connection.get_last_command().unwrap().arg;
-                    let entry = replay_logs.get(last_command);
-
-                    if let Some(entry) = entry {
-                        if let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(entry) {
-                            let to_transmit =
-                                connection.partial_replay_bytes.as_ref().unwrap_or(&bytes);
-                            let len = to_transmit.len().min(buf.len());
-                            buf[..len].copy_from_slice(&to_transmit[..len]);
-
-                            if len < to_transmit.len() {
-                                connection.partial_replay_bytes = Some(to_transmit[len..].to_vec());
-                            } else {
-                                connection.partial_replay_bytes = None;
-                            }
-
-                            return Some(len);
-                        }
-                    }
-                }
-                sleep(Duration::from_millis(100));
-                debug!(
-                    "Timed out waiting for cache population for {}",
-                    connection.get_last_command().unwrap().arg
-                );
+    let bytes = connection.partial_replay_bytes.take()?;
+
+    let len = bytes.len().min(buf.len());
+    buf[..len].copy_from_slice(&bytes[..len]);
+    if len < bytes.len() {
+        connection.partial_replay_bytes = Some(bytes[len..].to_vec());
+    }
+
+    Some(len)
+}
+
+/// How the last command of the client is answered while replaying.
+#[cfg(feature = "replay")]
+enum Replay {
+    /// The command is sent to the server.
+    Forward,
+    /// Recorded response, sent to the client in place of the server's.
+    Response(Vec<u8>),
+    /// Nothing was recorded for the command, the client is sent this ERR packet instead.
+    Error(Packet),
+    /// Nothing was recorded for the command and the client connection is closed.
+    Fail,
+}
+
+#[cfg(feature = "replay")]
+fn replay_response(connection: &mut Connection, packets: &[Packet]) -> Replay {
+    let client_flag = connection
+        .get_handshake_response()
+        .map(|handshake_response| handshake_response.client_flag)
+        .unwrap_or_default();
+    // Replayed packets are numbered after the command, whatever their sequence ids were when recorded.
+    let sequence = packets.last().map_or(0, |packet| packet.header.seq) + 1;
+
+    let offline = replay::offline_enabled();
+    if offline {
+        if let Some(response) = impersonate_server(connection, sequence, client_flag) {
+            return response;
+        }
+    }
+
+    let replay_logs = match &connection.replay {
+        Some(replay_logs) if offline || !send_command_to_server(connection) => replay_logs,
+        _ => return Replay::Forward,
+    };
+
+    let command = connection.get_last_command().unwrap().clone();
+    let key = replay_key(&command.arg);
+    let policy = miss::policy(&command);
+
+    let wait = match policy {
+        MissPolicy::Wait(milliseconds) => milliseconds,
+        _ => 0,
+    };
+    let deadline = Instant::now() + Duration::from_millis(wait);
+
+    loop {
+        match sequence::next_response(
+            &replay_logs.lookup(&key),
+            &key,
+            &mut connection.replay_positions,
+        )
+        .map(|entry| response::encode(&entry.response, sequence, client_flag))
+        {
+            Some(Ok(bytes)) if !bytes.is_empty() => return Replay::Response(bytes),
+            Some(Ok(_)) => {
+                error!("Empty recorded response for {}", command.arg);
+                break;
+            }
+            Some(Err(e)) => {
+                error!("Invalid recorded response for {}: {}", command.arg, e);
+                break;
             }
+            None if Instant::now() < deadline => sleep(Duration::from_millis(100)),
+            None => break,
         }
     }
-    None
+
+    miss::report(&command, &key);
+
+    match policy {
+        // There is no server to forward to when offline.
+        MissPolicy::Forward if !offline => Replay::Forward,
+        MissPolicy::Fail => Replay::Fail,
+        _ => Replay::Error(
+            ErrorData::new(
+                *miss::REPLAY_MISS_ERROR_CODE,
+                "HY000",
+                miss::REPLAY_MISS_ERROR_MESSAGE.clone(),
+            )
+            .to_packet(sequence, client_flag),
+        ),
+    }
+}
+
+/// Answers the client in place of the server when replaying offline, except for queries which are replayed from
+/// the recorded sessions. Any authentication succeeds, COM_PING, COM_INIT_DB and COM_RESET_CONNECTION succeed without
+/// effect and other commands fail.
+#[cfg(feature = "replay")]
+fn impersonate_server(connection: &Connection, sequence: u8, client_flag: u32) -> Option<Replay> {
+    let ok = |status_flags: u16| {
+        let ok_data = OkData {
+            header: 0x00,
+            affected_rows: 0,
+            last_insert_id: 0,
+            status_flags: Some(status_flags),
+            warnings: Some(0),
+            info: None,
+            session_state_info: None,
+        };
+        Replay::Response(ok_data.to_packet(sequence, client_flag).to_bytes())
+    };
+    let error = |message: String| {
+        Replay::Error(
+            ErrorData::new(*miss::REPLAY_MISS_ERROR_CODE, "HY000", message)
+                .to_packet(sequence, client_flag),
+        )
+    };
+
+    // The transaction state follows the responses sent to the client, including replayed ones.
+    let status_flags = connection.transaction.status_flags();
+
+    match connection.phase {
+        Phase::AuthInit | Phase::AuthComplete => Some(ok(status_flags)),
+        Phase::PendingResponse => match connection.get_last_command()?.com_code {
+            ComQuery => None,
+            MySqlCommand::ComPing | MySqlCommand::ComInitDb => Some(ok(status_flags)),
+            // Resetting the connection ends the transaction and enables autocommit.
+            MySqlCommand::ComResetConnection => Some(ok(Transaction::default().status_flags())),
+            MySqlCommand::ComStmtPrepare
+            | MySqlCommand::ComStmtExecute
+            | MySqlCommand::ComStmtFetch => Some(error(
+                "Prepared statements can't be replayed offline".to_string(),
+            )),
+            com_code => Some(error(format!("{:?} can't be replayed offline", com_code))),
+        },
+        // No response is expected.
+        _ => Some(Replay::Forward),
+    }
 }
 
-fn delay_if_required(last_command_option: &Option<Command>, delay_vars: &HashMap<String, String>) {
+fn delay_if_required(last_command_option: &Option<Command>) {
     if let Some(last_command) = last_command_option {
         if let Some(command_type) = last_command.arg.split_whitespace().next() {
-            let key = "DELAY_".to_string() + &*command_type.to_uppercase();
-            if delay_vars.contains_key(&key) {
-                match env::var(&key) {
-                    Ok(_) => {
-                        let delay = u64::from_str(delay_vars.get(&key).unwrap());
-
-                        match delay {
-                            Ok(delay) => {
-                                sleep(Duration::from_millis(delay));
-                                debug!("Delaying for {}", delay);
-                            }
-                            Err(_) => {
-                                error!("Delaying for {} failed", key);
-                            }
-                        }
-                    }
-                    Err(_) => {
-                        error!("DELAY_{key}: environment variable not found");
-                    }
-                }
+            let delay = DELAYS
+                .read()
+                .unwrap()
+                .get(&command_type.to_uppercase())
+                .copied();
+
+            if let Some(delay) = delay {
+                debug!("Delaying for {}", delay);
+                sleep(Duration::from_millis(delay));
             }
         }
     }
 }
 
+pub fn delays() -> HashMap<String, u64> {
+    DELAYS.read().unwrap().clone()
+}
+
+/// Sets the delay of commands starting with `command`, or removes it if `None`.
+pub fn set_delay(command: &str, delay: Option<u64>) {
+    let mut delays = DELAYS.write().unwrap();
+    match delay {
+        Some(delay) => delays.insert(command.to_uppercase(), delay),
+        None => delays.remove(&command.to_uppercase()),
+    };
+}
+
 #[cfg(feature = "tls")]
 fn switch_to_tls(mut connection: Connection) -> Connection {
     let server_tls = handle_server_tls();
@@ -342,8 +579,9 @@ pub fn read_bytes(conn: &mut SwitchableConnection, buf: &mut [u8]) -> Result<usi
         SwitchableConnection::ClientTls(stream_owned) => stream_owned.get_mut().read(buf),
         #[cfg(feature = "tls")]
         SwitchableConnection::ServerTls(stream_owned) => stream_owned.get_mut().read(buf),
-        #[cfg(test)]
-        SwitchableConnection::None => unreachable!(),
+        // As if the connection was closed.
+        #[cfg(any(test, feature = "replay"))]
+        SwitchableConnection::None => Ok(0),
     }
 }
 
@@ -354,34 +592,54 @@ pub fn write_bytes(conn: &mut SwitchableConnection, buf: &[u8]) {
         SwitchableConnection::ClientTls(stream_owned) => stream_owned.get_mut().write_all(buf),
         #[cfg(feature = "tls")]
         SwitchableConnection::ServerTls(stream_owned) => stream_owned.get_mut().write_all(buf),
-        #[cfg(test)]
-        SwitchableConnection::None => unreachable!(),
+        #[cfg(any(test, feature = "replay"))]
+        SwitchableConnection::None => Ok(()),
     };
 }
 
-fn get_write_response(last_command: Command, sequence: &u8, client_flag: u32) -> Option<Vec<u8>> {
-    let count = GLOBAL_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+fn get_write_response(
+    inserted: InsertedRows,
+    affected_rows: Option<AffectedRows>,
+    status_flags: u16,
+    sequence: &u8,
+    client_flag: u32,
+) -> Option<Vec<u8>> {
     let ok_data = OkData {
         header: 0x00,
-        affected_rows: 1,
-        last_insert_id: match last_command.arg.to_lowercase().starts_with("insert ") {
-            true => count as u64,
-            false => 0,
-        },
-        status_flags: None,
+        affected_rows: inserted.count
+            + match &affected_rows {
+                Some(AffectedRows {
+                    matched,
+                    changed: Some(changed),
+                }) if client_flag & CapabilityFlags::ClientFoundRows as u32 == 0 => *changed,
+                Some(AffectedRows { matched, .. }) => *matched,
+                None => 0,
+            },
+        last_insert_id: inserted.last_insert_id.unwrap_or(0),
+        status_flags: Some(status_flags),
         warnings: None,
-        info: None,
+        info: match &affected_rows {
+            Some(AffectedRows {
+                matched,
+                changed: Some(changed),
+            }) => Some(format!(
+                "Rows matched: {}  Changed: {}  Warnings: 0",
+                matched, changed
+            )),
+            // MySQL only reports the records of INSERTs with several rows.
+            None if inserted.count > 1 => Some(format!(
+                "Records: {}  Duplicates: 0  Warnings: 0",
+                inserted.count
+            )),
+            _ => None,
+        },
         session_state_info: None,
     };
 
     Some(ok_data.to_packet(sequence + 1, client_flag).to_bytes())
 }
 
-fn is_write_query(
-    last_command: &Option<Command>,
-    packet: &Packet,
-    diff: &mut StateDiffLog,
-) -> bool {
+fn is_write_query(last_command: &Option<Command>, packet: &Packet) -> bool {
     if last_command.is_none() {
         return false;
     }
@@ -389,19 +647,56 @@ fn is_write_query(
     let last_command = last_command.as_ref().unwrap();
     let last_command_arg = &last_command.arg.to_lowercase();
 
-    let ret = packet.p_type.eq(&PacketType::Command)
+    packet.p_type.eq(&PacketType::Command)
         && last_command.com_code.eq(&ComQuery)
         && (last_command_arg.starts_with("insert")
             || last_command_arg.starts_with("update")
-            || last_command_arg.starts_with("delete"));
+            || last_command_arg.starts_with("delete"))
+}
 
-    materialization::get_diff(diff, &last_command.ast);
+pub fn intercept_enabled() -> bool {
+    INTERCEPT_WRITES.load(Ordering::Relaxed)
+}
 
-    ret
+pub fn set_intercept_enabled(enabled: bool) {
+    INTERCEPT_WRITES.store(enabled, Ordering::Relaxed);
 }
 
-fn intercept_enabled() -> bool {
-    *INTERCEPT_WRITES == "true"
+/// Answers SELECTs on tables which only exist in the proxy, returning true if the last command was one of them.
+fn answer_local_query(connection: &mut Connection, packets: &[Packet]) -> bool {
+    if connection.phase != Phase::PendingResponse || packets.len() != 1 {
+        return false;
+    }
+
+    let result_set = match connection.get_last_command() {
+        Some(command) if command.com_code == ComQuery => {
+            match materialization::local::select(connection, &command.ast) {
+                Some(result_set) => result_set,
+                None => return false,
+            }
+        }
+        _ => return false,
+    };
+
+    let client_flag = connection
+        .get_handshake_response()
+        .map(|handshake_response| handshake_response.client_flag)
+        .unwrap_or_default();
+    let sequence = packets.first().unwrap().header.seq + 1;
+
+    let response = match result_set {
+        Ok(result_set) => result_set.to_packets(sequence, client_flag),
+        Err(e) => {
+            debug!("Unable to answer the query from the local table, {}", e);
+            // ER_NOT_SUPPORTED_YET
+            vec![ErrorData::new(1235, "42000", e).to_packet(sequence, client_flag)]
+        }
+    };
+
+    connection.phase = Phase::Command;
+    let bytes: Vec<u8> = response.iter().flat_map(Packet::to_bytes).collect();
+    write_bytes(&mut connection.client_connection, &bytes);
+    true
 }
 
 fn intercept_command(connection: &mut Connection, packets: &[Packet]) -> bool {
@@ -415,23 +710,23 @@ fn intercept_command(connection: &mut Connection, packets: &[Packet]) -> bool {
         .as_ref()
         .map(|hr| hr.client_flag);
 
-    if packets.len() == 1
-        && is_write_query(
-            &last_command,
-            packets.first().unwrap(),
-            &mut connection.diff,
-        )
-    {
+    if packets.len() == 1 && is_write_query(&last_command, packets.first().unwrap()) {
         let last_command = last_command.unwrap();
 
+        let (affected_rows, inserted) =
+            materialization::record_write(connection, &last_command.ast);
+
         if let Some(response) = get_write_response(
-            last_command,
+            inserted,
+            affected_rows,
+            connection.transaction.status_flags(),
             &packets.first().unwrap().header.seq,
             client_flag.unwrap(),
         ) {
             debug!("{:?}", response);
             connection.phase = Phase::Command;
             write_bytes(&mut connection.client_connection, &response);
+            stats::increment(&STATS.intercepted_writes);
             return true;
         }
     }
@@ -0,0 +1,368 @@
+use crate::connection::CONNECTIONS;
+use crate::connection_handler::{delays, intercept_enabled, set_delay, set_intercept_enabled};
+use crate::materialization::namespace::Namespaces;
+use crate::materialization::{clear_divergences, divergence_count, export, StateDiffLog};
+use crate::stats::STATS;
+use log::{debug, error, info};
+use once_cell::sync::Lazy;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::{env, thread};
+
+/// Address of the HTTP control API, loopback only by default. An empty value disables the API.
+static CONTROL_ADDRESS: Lazy<String> =
+    Lazy::new(|| env::var("CONTROL_ADDRESS").unwrap_or_else(|_| "127.0.0.1:6034".to_string()));
+
+/// Requests larger than this are rejected, the API only ever receives small JSON documents.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Time allowed to read a request or write a response, so that stalled clients don't hold on to their thread.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default, PartialEq)]
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    body: String,
+}
+
+/// Starts serving the control API in the background, unless disabled.
+pub fn start(namespaces: &Namespaces) {
+    if CONTROL_ADDRESS.is_empty() {
+        return;
+    }
+
+    let listener = match TcpListener::bind(CONTROL_ADDRESS.as_str()) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(
+                "Unable to bind the control API to {}: {}",
+                *CONTROL_ADDRESS, e
+            );
+            return;
+        }
+    };
+    info!("Control API listening on {}", *CONTROL_ADDRESS);
+
+    let namespaces = namespaces.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let namespaces = namespaces.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve(&namespaces, stream) {
+                    debug!("Control API request failed: {}", e);
+                }
+            });
+        }
+    });
+}
+
+fn serve(namespaces: &Namespaces, mut stream: TcpStream) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| e.to_string())?;
+
+    let (status, body) = match read_request(&mut stream) {
+        Ok(request) => {
+            debug!("Control API request {} {}", request.method, request.path);
+            route(namespaces, &request)
+        }
+        Err(e) => (400, json!({ "error": e })),
+    };
+
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+
+    stream
+        .write_all(response.as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+fn read_request(stream: impl Read) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut request_line = line.split_whitespace();
+    let method = request_line.next().ok_or("Empty request")?.to_uppercase();
+    let target = request_line.next().ok_or("Missing request target")?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|e| e.to_string())? == 0 {
+            break;
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| "Invalid Content-Length")?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_SIZE {
+        return Err("Request body too large".to_string());
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+    Ok(Request {
+        method,
+        path: path.trim_end_matches('/').to_string(),
+        query,
+        body: String::from_utf8(body).map_err(|e| e.to_string())?,
+    })
+}
+
+fn route(namespaces: &Namespaces, request: &Request) -> (u16, Value) {
+    let namespace = request.query.get("namespace").map(String::as_str);
+    let table = request.query.get("table").map(String::as_str);
+
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/diffs") => Ok(list_diffs(namespaces, namespace, table)),
+        ("DELETE", "/diffs") => {
+            let cleared: usize = selected_logs(namespaces, namespace)
+                .iter()
+                .map(|(_, diff)| clear_divergences(diff, table))
+                .sum();
+            Ok(json!({ "cleared": cleared }))
+        }
+        ("GET", "/intercept") => Ok(json!({ "enabled": intercept_enabled() })),
+        ("PUT", "/intercept") => parse_body(request).and_then(|body| {
+            let enabled = body
+                .get("enabled")
+                .and_then(Value::as_bool)
+                .ok_or("Expected {\"enabled\": true|false}")?;
+            set_intercept_enabled(enabled);
+            Ok(json!({ "enabled": enabled }))
+        }),
+        ("GET", "/delays") => Ok(json!(delays())),
+        ("PUT", "/delays") => parse_body(request).and_then(|body| {
+            let body = body
+                .as_object()
+                .ok_or("Expected {\"COMMAND\": milliseconds|null}")?;
+            for (command, delay) in body {
+                match delay {
+                    Value::Null => set_delay(command, None),
+                    delay => set_delay(
+                        command,
+                        Some(delay.as_u64().ok_or("Delays must be milliseconds")?),
+                    ),
+                }
+            }
+            Ok(json!(delays()))
+        }),
+        ("GET", "/connections") => {
+            let mut connections: Vec<_> = CONNECTIONS
+                .iter()
+                .map(|entry| entry.value().clone())
+                .collect();
+            connections.sort_by_key(|connection| connection.id);
+            Ok(json!(connections))
+        }
+        ("GET", "/stats") => Ok(stats(namespaces)),
+        _ => return (404, json!({ "error": "Not found" })),
+    };
+
+    match result {
+        Ok(value) => (200, value),
+        Err(e) => (400, json!({ "error": e })),
+    }
+}
+
+/// Diffs of the selected namespaces, as their sequence number, table and equivalent SQL statement.
+fn list_diffs(namespaces: &Namespaces, namespace: Option<&str>, table: Option<&str>) -> Value {
+    let mut diffs = Map::new();
+
+    for (namespace, diff) in selected_logs(namespaces, namespace) {
+        let statements: Vec<Value> = export::diff_statements(&diff, table)
+            .into_iter()
+            .map(|(sequence, table, statement)| {
+                json!({ "sequence": sequence, "table": table, "statement": statement })
+            })
+            .collect();
+        diffs.insert(namespace, Value::Array(statements));
+    }
+
+    Value::Object(diffs)
+}
+
+fn stats(namespaces: &Namespaces) -> Value {
+    let divergences: Map<String, Value> = selected_logs(namespaces, None)
+        .into_iter()
+        .map(|(namespace, diff)| (namespace, json!(divergence_count(&diff))))
+        .collect();
+
+    json!({
+        "connections": STATS.connections.load(Ordering::Relaxed),
+        "open_connections": CONNECTIONS.len(),
+        "commands": STATS.commands.load(Ordering::Relaxed),
+        "intercepted_writes": STATS.intercepted_writes.load(Ordering::Relaxed),
+        "admin_commands": STATS.admin_commands.load(Ordering::Relaxed),
+        "replay_misses": STATS.replay_misses.load(Ordering::Relaxed),
+        "intercept": intercept_enabled(),
+        "divergences": divergences,
+    })
+}
+
+/// Diff log of the given namespace, or of every namespace if none is given.
+fn selected_logs(namespaces: &Namespaces, namespace: Option<&str>) -> Vec<(String, StateDiffLog)> {
+    namespaces
+        .iter()
+        .filter(|entry| namespace.is_none_or(|namespace| namespace == entry.key()))
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect()
+}
+
+fn parse_body(request: &Request) -> Result<Value, String> {
+    serde_json::from_str(&request.body).map_err(|e| e.to_string())
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control::{read_request, route, Request};
+    use crate::materialization::namespace::{diff_log, Namespaces};
+    use crate::materialization::{get_diff, TableStore};
+    use crate::mysql::command::{Command, MySqlCommand};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_read_request() {
+        let request = read_request(
+            &b"PUT /delays/?namespace=worker%201&table=shop.account HTTP/1.1\r\nHost: localhost\r\nContent-Length: 15\r\n\r\n{\"SELECT\": 500}"[..],
+        )
+        .unwrap();
+
+        assert_eq!(
+            Request {
+                method: "PUT".to_string(),
+                path: "/delays".to_string(),
+                query: HashMap::from([
+                    ("namespace".to_string(), "worker 1".to_string()),
+                    ("table".to_string(), "shop.account".to_string()),
+                ]),
+                body: "{\"SELECT\": 500}".to_string(),
+            },
+            request
+        );
+    }
+
+    #[test]
+    fn test_diff_endpoints() {
+        let namespaces = Namespaces::default();
+        get_diff(
+            &mut diff_log(&namespaces, "worker-1"),
+            &TableStore::default(),
+            Some("shop"),
+            &Command::from_bytes(MySqlCommand::ComQuery, b"delete from account where id = 1").ast,
+        );
+        diff_log(&namespaces, "worker-2");
+
+        let request = |method: &str, path: &str, query: &[(&str, &str)]| Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            body: String::new(),
+        };
+
+        let (status, diffs) = route(&namespaces, &request("GET", "/diffs", &[]));
+        assert_eq!(200, status);
+        assert_eq!(
+            json!({
+                "worker-1": [{
+                    "sequence": diffs["worker-1"][0]["sequence"],
+                    "table": "shop.account",
+                    "statement": "DELETE FROM `shop`.`account` WHERE id = 1;",
+                }],
+                "worker-2": [],
+            }),
+            diffs
+        );
+
+        assert_eq!(
+            (200, json!({ "cleared": 0 })),
+            route(
+                &namespaces,
+                &request("DELETE", "/diffs", &[("namespace", "worker-2")])
+            )
+        );
+        assert_eq!(
+            (200, json!({ "cleared": 1 })),
+            route(
+                &namespaces,
+                &request("DELETE", "/diffs", &[("table", "shop.account")])
+            )
+        );
+        assert_eq!(404, route(&namespaces, &request("GET", "/unknown", &[])).0);
+    }
+}
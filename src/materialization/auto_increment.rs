@@ -0,0 +1,177 @@
+use crate::connection::Connection;
+use crate::materialization::{
+    local_table_key, schema_name, table_key, unqualified_name, upstream, LocalTable, Row,
+};
+use log::{debug, warn};
+use sqlparser::ast::{Statement, TableObject};
+
+/// Makes sure every table targeted by an INSERT in `ast` has a known auto-increment counter.
+/// Counters are seeded once per table from the upstream server, so that generated ids continue
+/// from the real sequence instead of colliding with existing rows.
+pub fn prepare_counters(connection: &mut Connection, ast: &Option<Vec<Statement>>) {
+    let statements = match ast {
+        Some(statements) => statements,
+        None => return,
+    };
+
+    for statement in statements {
+        let name = match statement {
+            Statement::Insert(insert) => match &insert.table {
+                TableObject::TableName(name) => name,
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        let table_name = unqualified_name(name);
+        let key = local_table_key(
+            &connection.tables,
+            &table_key(name, connection.current_database.as_deref()),
+        );
+        let schema = match schema_name(name) {
+            Some(schema) => upstream::string_literal(&schema),
+            None => "DATABASE()".to_string(),
+        };
+
+        let synced = connection
+            .tables
+            .get(&key)
+            .is_some_and(|table| table.auto_increment_synced);
+        if synced {
+            continue;
+        }
+
+        let mut column = connection
+            .tables
+            .get(&key)
+            .and_then(|table| table.auto_increment.clone());
+
+        if column.is_none() {
+            column = single_value(
+                connection,
+                &format!(
+                    "SELECT COLUMN_NAME FROM information_schema.COLUMNS \
+                     WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} \
+                     AND EXTRA LIKE '%auto_increment%'",
+                    schema,
+                    upstream::string_literal(&table_name)
+                ),
+            );
+        }
+
+        let column = match column {
+            Some(column) => column,
+            None => {
+                debug!("No auto-increment column found for {}", table_name);
+                connection
+                    .tables
+                    .entry(key)
+                    .or_default()
+                    .auto_increment_synced = true;
+                continue;
+            }
+        };
+
+        let auto_increment = single_value(
+            connection,
+            &format!(
+                "SELECT AUTO_INCREMENT FROM information_schema.TABLES \
+                 WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {}",
+                schema,
+                upstream::string_literal(&table_name)
+            ),
+        )
+        .and_then(|v| v.parse::<u64>().ok());
+
+        let max_id = single_value(
+            connection,
+            &format!(
+                "SELECT MAX({}) FROM {}",
+                upstream::quoted_identifier(&column),
+                name
+            ),
+        )
+        .and_then(|v| v.parse::<u64>().ok());
+
+        let mut table = connection.tables.entry(key).or_default();
+        let local_next = table.next_auto_increment;
+
+        table.auto_increment = Some(column);
+        table.auto_increment_synced = true;
+        table.next_auto_increment = [auto_increment, max_id.map(|id| id + 1), local_next]
+            .into_iter()
+            .flatten()
+            .max()
+            .or(Some(1));
+    }
+}
+
+/// Assigns the next auto-increment value to `row` unless it carries an explicit id, returning
+/// the generated id. Explicit ids move the counter forward past them, as MySQL does.
+pub fn assign_id(table: &mut LocalTable, row: &mut Row) -> Option<u64> {
+    let column = table.auto_increment.clone()?;
+    let next = table.next_auto_increment.unwrap_or(1);
+
+    let explicit_id = match row.get(&column) {
+        Some(Some(value)) => match value.parse::<u64>() {
+            Ok(0) => None,
+            Ok(id) => Some(id),
+            Err(_) => return None,
+        },
+        _ => None,
+    };
+
+    match explicit_id {
+        Some(id) => {
+            table.next_auto_increment = Some(next.max(id + 1));
+            None
+        }
+        None => {
+            table.next_auto_increment = Some(next + 1);
+            row.insert(column, Some(next.to_string()));
+            Some(next)
+        }
+    }
+}
+
+fn single_value(connection: &mut Connection, sql: &str) -> Option<String> {
+    match upstream::query(connection, sql) {
+        Ok(result) => result
+            .rows
+            .into_iter()
+            .next()
+            .and_then(|row| row.into_values().next())
+            .flatten(),
+        Err(e) => {
+            warn!("Unable to query upstream: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::materialization::auto_increment::assign_id;
+    use crate::materialization::{LocalTable, Row};
+
+    #[test]
+    fn test_assign_id() {
+        let mut table = LocalTable {
+            auto_increment: Some("id".to_string()),
+            next_auto_increment: Some(5),
+            ..LocalTable::default()
+        };
+
+        let mut row = Row::new();
+        assert_eq!(Some(5), assign_id(&mut table, &mut row));
+        assert_eq!(Some(&Some("5".to_string())), row.get("id"));
+
+        let mut row = Row::from([("id".to_string(), Some("10".to_string()))]);
+        assert_eq!(None, assign_id(&mut table, &mut row));
+        assert_eq!(Some(11), table.next_auto_increment);
+
+        let mut row = Row::from([("id".to_string(), Some("0".to_string()))]);
+        assert_eq!(Some(11), assign_id(&mut table, &mut row));
+        assert_eq!(Some(12), table.next_auto_increment);
+    }
+}
@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Collation ids keyed by column name, as reported in the column definitions of a result set.
+pub type Collations = HashMap<String, u16>;
+
+/// utf8mb4_0900_ai_ci, the server default, used for literals and columns without metadata.
+pub const DEFAULT_COLLATION_ID: u16 = 255;
+
+/// Base letters of U+00C0..U+017F, used to strip accents in accent-insensitive collations.
+const LATIN_BASE_LETTERS: &str = "AAAAAAÆCEEEEIIIIÐNOOOOO×OUUUUYÞßaaaaaaæceeeeiiiiðnooooo÷ouuuuyþy\
+                                  AaAaAaCcCcCcCcDdDdEeEeEeEeEeGgGgGgGgHhHhIiIiIiIiIıĲĳJjKkĸLlLlLlL\
+                                  lLlNnNnNnŉŊŋOoOoOoŒœRrRrRrSsSsSsSsTtTtTtUuUuUuUuUuUuWwYyYZzZzZzſ";
+
+/// Comparison rules of a MySQL collation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collation {
+    /// Compares code points, ignoring case and accent rules.
+    pub binary: bool,
+    pub case_sensitive: bool,
+    pub accent_sensitive: bool,
+    /// PAD SPACE collations ignore trailing spaces, NO PAD collations (the 0900 family and binary) don't.
+    pub pad_space: bool,
+}
+
+impl Collation {
+    pub fn from_id(id: u16) -> Collation {
+        let (binary, case_sensitive, accent_sensitive, pad_space) = match id {
+            // binary, utf8mb4_0900_bin
+            63 | 309 => (true, true, true, false),
+            // latin1_bin, utf8mb3_bin, utf8mb4_bin
+            47 | 83 | 46 => (true, true, true, true),
+            // utf8mb4_0900_ai_ci
+            255 => (false, false, false, false),
+            // utf8mb4_0900_as_ci
+            305 => (false, false, true, false),
+            // utf8mb4_0900_as_cs
+            278 => (false, true, true, false),
+            // latin1_general_ci
+            48 => (false, false, true, true),
+            // latin1_general_cs
+            49 => (false, true, true, true),
+            // latin1_german1_ci, latin1_swedish_ci, latin1_danish_ci, latin1_german2_ci, latin1_spanish_ci,
+            // utf8mb3_general_ci, utf8mb4_general_ci, utf8mb3_unicode_ci, utf8mb4_unicode_ci.
+            // Language specific letters (e.g, Å in Swedish) are approximated by their base letter.
+            5 | 8 | 15 | 31 | 94 | 33 | 45 | 192 | 224 => (false, false, false, true),
+            _ => return Collation::from_id(DEFAULT_COLLATION_ID),
+        };
+
+        Collation {
+            binary,
+            case_sensitive,
+            accent_sensitive,
+            pad_space,
+        }
+    }
+
+    pub fn compare(&self, l: &str, r: &str) -> Ordering {
+        let (l, r) = match self.pad_space {
+            true => (l.trim_end_matches(' '), r.trim_end_matches(' ')),
+            false => (l, r),
+        };
+
+        if self.binary {
+            return l.cmp(r);
+        }
+
+        // Compare on base letters first, then accents and finally case, as far as the collation is sensitive to them.
+        let primary = fold(l, true, true).cmp(&fold(r, true, true));
+        let secondary = || match self.accent_sensitive {
+            true => fold(l, true, false).cmp(&fold(r, true, false)),
+            false => Ordering::Equal,
+        };
+        let tertiary = || match self.case_sensitive {
+            true => {
+                fold(l, false, !self.accent_sensitive).cmp(&fold(r, false, !self.accent_sensitive))
+            }
+            false => Ordering::Equal,
+        };
+
+        primary.then_with(secondary).then_with(tertiary)
+    }
+
+    /// Normalized form of the value, equal for values the collation compares as equal.
+    pub fn key(&self, value: &str) -> String {
+        let value = match self.pad_space {
+            true => value.trim_end_matches(' '),
+            false => value,
+        };
+
+        match self.binary {
+            true => value.to_string(),
+            false => fold(value, !self.case_sensitive, !self.accent_sensitive),
+        }
+    }
+
+    /// Character equality used for LIKE matching, which never applies padding.
+    pub fn chars_equal(&self, l: char, r: char) -> bool {
+        if self.binary {
+            return l == r;
+        }
+
+        fold_char(l, !self.case_sensitive, !self.accent_sensitive).eq(fold_char(
+            r,
+            !self.case_sensitive,
+            !self.accent_sensitive,
+        ))
+    }
+}
+
+impl Default for Collation {
+    fn default() -> Self {
+        Collation::from_id(DEFAULT_COLLATION_ID)
+    }
+}
+
+fn fold(value: &str, lowercase: bool, strip_accents: bool) -> String {
+    value
+        .chars()
+        .flat_map(|c| fold_char(c, lowercase, strip_accents))
+        .collect()
+}
+
+fn fold_char(c: char, lowercase: bool, strip_accents: bool) -> impl Iterator<Item = char> {
+    let c = match strip_accents && ('\u{c0}'..='\u{17f}').contains(&c) {
+        true => LATIN_BASE_LETTERS
+            .chars()
+            .nth(c as usize - 0xc0)
+            .unwrap_or(c),
+        false => c,
+    };
+
+    let lowered = match lowercase {
+        true => Some(c.to_lowercase()),
+        false => None,
+    };
+
+    let original = match lowercase {
+        true => None,
+        false => Some(c),
+    };
+
+    lowered.into_iter().flatten().chain(original)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::materialization::collation::Collation;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_collation_compare() {
+        let ai_ci = Collation::from_id(255);
+        assert_eq!(Ordering::Equal, ai_ci.compare("active", "ACTIVE"));
+        assert_eq!(Ordering::Equal, ai_ci.compare("Crème", "creme"));
+        assert_eq!(Ordering::Less, ai_ci.compare("apple", "Banana"));
+        assert_eq!(Ordering::Greater, ai_ci.compare("a ", "a"));
+
+        let general_ci = Collation::from_id(45);
+        assert_eq!(Ordering::Equal, general_ci.compare("a ", "A"));
+
+        let as_ci = Collation::from_id(305);
+        assert_eq!(Ordering::Equal, as_ci.compare("CRÈME", "crème"));
+        assert_ne!(Ordering::Equal, as_ci.compare("creme", "crème"));
+
+        let as_cs = Collation::from_id(278);
+        assert_ne!(Ordering::Equal, as_cs.compare("active", "ACTIVE"));
+        assert_eq!(Ordering::Less, as_cs.compare("apple", "Banana"));
+
+        let bin = Collation::from_id(46);
+        assert_eq!(Ordering::Greater, bin.compare("apple", "Banana"));
+        assert!(!bin.chars_equal('a', 'A'));
+        assert!(ai_ci.chars_equal('é', 'E'));
+    }
+}
@@ -1,16 +1,53 @@
-use sqlparser::ast::{BinaryOperator, Expr, Value};
+use crate::materialization::collation::{Collation, Collations};
+use crate::materialization::json;
+use sqlparser::ast::{
+    BinaryOperator, Expr, Function, FunctionArg, FunctionArgExpr, FunctionArguments, UnaryOperator,
+    Value,
+};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
 
 #[derive(Debug)]
 pub enum ParseResult {
     Boolean(bool),
     String(Option<String>),
+    Json(serde_json::Value),
+}
+
+impl ParseResult {
+    /// Whether the result is TRUE as a condition, e.g, of a WHERE clause. Following SQL three-valued logic, FALSE and
+    /// NULL (UNKNOWN) both leave the row out. Strings are cast to a number, selecting the row unless it is 0.
+    pub fn is_true(&self) -> bool {
+        match self {
+            ParseResult::Boolean(value) => *value,
+            ParseResult::String(None) | ParseResult::Json(serde_json::Value::Null) => false,
+            ParseResult::String(Some(value)) => Number::from(value.as_str()).as_f64() != 0.0,
+            ParseResult::Json(serde_json::Value::Bool(value)) => *value,
+            ParseResult::Json(serde_json::Value::Number(value)) => {
+                value.as_f64().is_some_and(|value| value != 0.0)
+            }
+            ParseResult::Json(_) => false,
+        }
+    }
+
+    /// Converts the result into the value MySQL would store, booleans being represented as 1 and 0.
+    pub fn into_value(self) -> Option<String> {
+        match self {
+            ParseResult::Boolean(true) => Some("1".to_string()),
+            ParseResult::Boolean(false) => Some("0".to_string()),
+            ParseResult::String(value) => value,
+            ParseResult::Json(value) => Some(json::to_mysql_string(&value)),
+        }
+    }
 }
 
 pub trait Parser {
     fn evaluate(
         row: &HashMap<String, Option<String>>,
-        expr: &Box<Expr>,
+        collations: &Collations,
+        expr: &Expr,
     ) -> Result<ParseResult, &'static str>;
 }
 
@@ -20,34 +57,83 @@ struct EvaluateConjunction {}
 
 struct EvaluateCondition {}
 
+struct EvaluatePredicate {}
+
+struct EvaluateCase {}
+
+struct EvaluateArithmetic {}
+
+struct EvaluateFunction {}
+
+struct EvaluateJson {}
+
 impl Parser for Parse {
     fn evaluate(
         row: &std::collections::HashMap<
             std::string::String,
             std::option::Option<std::string::String>,
         >,
-        expr: &std::boxed::Box<sqlparser::ast::Expr>,
+        collations: &Collations,
+        expr: &Expr,
     ) -> std::result::Result<ParseResult, &'static str> {
-        match &**expr {
+        match expr {
             Expr::BinaryOp { op, .. } => match op {
-                BinaryOperator::And | BinaryOperator::Or => {
-                    Ok(EvaluateConjunction::evaluate(row, expr)?)
+                BinaryOperator::And | BinaryOperator::Or | BinaryOperator::Xor => {
+                    Ok(EvaluateConjunction::evaluate(row, collations, expr)?)
                 }
                 BinaryOperator::Eq
                 | BinaryOperator::NotEq
                 | BinaryOperator::Lt
                 | BinaryOperator::LtEq
                 | BinaryOperator::Gt
-                | BinaryOperator::GtEq => Ok(EvaluateCondition::evaluate(row, expr)?),
+                | BinaryOperator::GtEq
+                | BinaryOperator::Spaceship => {
+                    Ok(EvaluateCondition::evaluate(row, collations, expr)?)
+                }
+                BinaryOperator::Plus
+                | BinaryOperator::Minus
+                | BinaryOperator::Multiply
+                | BinaryOperator::Divide
+                | BinaryOperator::Modulo
+                | BinaryOperator::MyIntegerDivide => {
+                    Ok(EvaluateArithmetic::evaluate(row, collations, expr)?)
+                }
+                BinaryOperator::Arrow | BinaryOperator::LongArrow => {
+                    Ok(EvaluateJson::evaluate(row, collations, expr)?)
+                }
 
-                _ => panic!(),
+                _ => Err("Unsupported operator"),
             },
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus | UnaryOperator::Plus,
+                ..
+            } => Ok(EvaluateArithmetic::evaluate(row, collations, expr)?),
+            Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                ..
+            }
+            | Expr::InList { .. }
+            | Expr::Between { .. }
+            | Expr::Like { .. }
+            | Expr::IsTrue(_)
+            | Expr::IsNotTrue(_)
+            | Expr::IsFalse(_)
+            | Expr::IsNotFalse(_)
+            | Expr::IsUnknown(_)
+            | Expr::IsNotUnknown(_) => Ok(EvaluatePredicate::evaluate(row, collations, expr)?),
+            Expr::Case { .. } => Ok(EvaluateCase::evaluate(row, collations, expr)?),
+            Expr::Function(function)
+                if function_name(function).is_some_and(|name| name.starts_with("JSON_")) =>
+            {
+                Ok(EvaluateJson::evaluate(row, collations, expr)?)
+            }
+            Expr::Function(_) => Ok(EvaluateFunction::evaluate(row, collations, expr)?),
             Expr::Value(val) => {
                 let str = &val.clone().into_string();
 
                 match str {
                     None => match &val.value {
-                        Value::Boolean(bool) => Ok(ParseResult::Boolean(bool.clone())),
+                        Value::Boolean(bool) => Ok(ParseResult::Boolean(*bool)),
                         Value::Number(number, _) => Ok(ParseResult::String(Some(number.clone()))),
                         Value::SingleQuotedString(value)
                         | Value::TripleSingleQuotedString(value)
@@ -77,20 +163,22 @@ impl Parser for Parse {
                 }
             }
             Expr::Identifier(identifier) => Ok(ParseResult::String(
-                row.get(&identifier.value).unwrap().clone(),
+                row.get(&identifier.value).ok_or("Unknown column")?.clone(),
             )),
             Expr::CompoundIdentifier(identifiers) => Ok(ParseResult::String(
-                row.get(&identifiers.last().unwrap().value).unwrap().clone(),
+                row.get(&identifiers.last().unwrap().value)
+                    .ok_or("Unknown column")?
+                    .clone(),
             )),
-            Expr::IsNull(expr) => match Parse::evaluate(row, expr)? {
-                ParseResult::Boolean(_) => Ok(ParseResult::Boolean(false)),
+            Expr::IsNull(expr) => match Parse::evaluate(row, collations, expr)? {
+                ParseResult::Boolean(_) | ParseResult::Json(_) => Ok(ParseResult::Boolean(false)),
                 ParseResult::String(value) => Ok(ParseResult::Boolean(value.is_none())),
             },
-            Expr::IsNotNull(expr) => match Parse::evaluate(row, expr)? {
-                ParseResult::Boolean(_) => Ok(ParseResult::Boolean(false)),
+            Expr::IsNotNull(expr) => match Parse::evaluate(row, collations, expr)? {
+                ParseResult::Boolean(_) | ParseResult::Json(_) => Ok(ParseResult::Boolean(true)),
                 ParseResult::String(value) => Ok(ParseResult::Boolean(value.is_some())),
             },
-            Expr::Nested(expr) => Parse::evaluate(row, expr),
+            Expr::Nested(expr) => Parse::evaluate(row, collations, expr),
             _ => Err("Unsupported expression"),
         }
     }
@@ -102,130 +190,895 @@ impl Parser for EvaluateConjunction {
             std::string::String,
             std::option::Option<std::string::String>,
         >,
-        expr: &std::boxed::Box<sqlparser::ast::Expr>,
+        collations: &Collations,
+        expr: &Expr,
     ) -> Result<ParseResult, &'static str> {
-        match &**expr {
-            Expr::BinaryOp { left, op, right } => match op {
-                BinaryOperator::And | BinaryOperator::Or => {
-                    let parsed_left = Parse::evaluate(row, left)?;
-
-                    match op {
-                        BinaryOperator::And => {
-                            if let ParseResult::Boolean(b) = parsed_left {
-                                if !b {
-                                    return Ok(ParseResult::Boolean(false));
-                                }
-                            }
-                        }
-                        BinaryOperator::Or => {
-                            if let ParseResult::Boolean(b) = parsed_left {
-                                if b {
-                                    return Ok(ParseResult::Boolean(true));
-                                }
-                            }
-                        }
+        let (left, op, right) = match expr {
+            Expr::BinaryOp { left, op, right } => (left, op, right),
+            _ => return Err("Unsupported expression"),
+        };
 
-                        _ => unreachable!(),
-                    }
+        let parsed_left = truth(Parse::evaluate(row, collations, left)?);
+
+        // Short-circuit when the left operand alone decides the outcome.
+        match (op, parsed_left) {
+            (BinaryOperator::And, Some(false)) => return Ok(ParseResult::Boolean(false)),
+            (BinaryOperator::Or, Some(true)) => return Ok(ParseResult::Boolean(true)),
+            _ => {}
+        }
 
-                    let parsed_right = Parse::evaluate(row, right)?;
+        let parsed_right = truth(Parse::evaluate(row, collations, right)?);
 
-                    match op {
-                        BinaryOperator::And => {
-                            if let ParseResult::Boolean(b) = parsed_right {
-                                return Ok(ParseResult::Boolean(b));
-                            };
-                            panic!()
-                        }
-                        BinaryOperator::Or => {
-                            if let ParseResult::Boolean(b) = parsed_right {
-                                return Ok(ParseResult::Boolean(b));
-                            };
-                            panic!()
+        let result = match op {
+            BinaryOperator::And => match (parsed_left, parsed_right) {
+                (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            BinaryOperator::Or => match (parsed_left, parsed_right) {
+                (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            BinaryOperator::Xor => match (parsed_left, parsed_right) {
+                (Some(l), Some(r)) => Some(l != r),
+                _ => None,
+            },
+            _ => return Err("Unsupported operator"),
+        };
+
+        Ok(from_truth(result))
+    }
+}
+
+impl Parser for EvaluateCondition {
+    fn evaluate(
+        row: &HashMap<String, Option<String>>,
+        collations: &Collations,
+        expr: &Expr,
+    ) -> Result<ParseResult, &'static str> {
+        let (left, op, right) = match expr {
+            Expr::BinaryOp { left, op, right } => (left, op, right),
+            _ => return Err("Unsupported expression"),
+        };
+
+        let parsed_left = Parse::evaluate(row, collations, left)?;
+        let parsed_right = Parse::evaluate(row, collations, right)?;
+
+        if let (ParseResult::Json(_), _) | (_, ParseResult::Json(_)) = (&parsed_left, &parsed_right)
+        {
+            return compare_json(op, (parsed_left, left), (parsed_right, right));
+        }
+
+        let parsed_left = parsed_left.into_value();
+        let parsed_right = parsed_right.into_value();
+
+        let collation = collation_of(collations, &[left, right]);
+
+        if let BinaryOperator::Spaceship = op {
+            return Ok(ParseResult::Boolean(match (&parsed_left, &parsed_right) {
+                (Some(l), Some(r)) => compare(&collation, l, r) == Ordering::Equal,
+                (l, r) => l.is_none() && r.is_none(),
+            }));
+        }
+
+        let ordering = match (parsed_left, parsed_right) {
+            (Some(l), Some(r)) => compare(&collation, &l, &r),
+            _ => return Ok(ParseResult::String(None)),
+        };
+
+        let result = match op {
+            BinaryOperator::Eq => ordering == Ordering::Equal,
+            BinaryOperator::NotEq => ordering != Ordering::Equal,
+            BinaryOperator::Lt => ordering == Ordering::Less,
+            BinaryOperator::LtEq => ordering != Ordering::Greater,
+            BinaryOperator::Gt => ordering == Ordering::Greater,
+            BinaryOperator::GtEq => ordering != Ordering::Less,
+            _ => return Err("Unsupported operator"),
+        };
+
+        Ok(ParseResult::Boolean(result))
+    }
+}
+
+impl Parser for EvaluatePredicate {
+    fn evaluate(
+        row: &HashMap<String, Option<String>>,
+        collations: &Collations,
+        expr: &Expr,
+    ) -> Result<ParseResult, &'static str> {
+        let result = match expr {
+            Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                expr,
+            } => truth(Parse::evaluate(row, collations, expr)?).map(|b| !b),
+            Expr::IsTrue(expr) => {
+                Some(truth(Parse::evaluate(row, collations, expr)?) == Some(true))
+            }
+            Expr::IsNotTrue(expr) => {
+                Some(truth(Parse::evaluate(row, collations, expr)?) != Some(true))
+            }
+            Expr::IsFalse(expr) => {
+                Some(truth(Parse::evaluate(row, collations, expr)?) == Some(false))
+            }
+            Expr::IsNotFalse(expr) => {
+                Some(truth(Parse::evaluate(row, collations, expr)?) != Some(false))
+            }
+            Expr::IsUnknown(expr) => Some(truth(Parse::evaluate(row, collations, expr)?).is_none()),
+            Expr::IsNotUnknown(expr) => {
+                Some(truth(Parse::evaluate(row, collations, expr)?).is_some())
+            }
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = Parse::evaluate(row, collations, expr)?.into_value();
+                let collation = collation_of(collations, &[expr]);
+                let mut result = Some(false);
+
+                if let Some(value) = value {
+                    for item in list {
+                        match Parse::evaluate(row, collations, item)?.into_value() {
+                            Some(item) if compare(&collation, &value, &item) == Ordering::Equal => {
+                                result = Some(true);
+                                break;
+                            }
+                            Some(_) => {}
+                            None => result = None,
                         }
-                        _ => unreachable!(),
                     }
+                } else {
+                    result = None;
+                }
+
+                result.map(|b| b != *negated)
+            }
+            Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => {
+                let collation = collation_of(collations, &[expr, low, high]);
+                let value = Parse::evaluate(row, collations, expr)?.into_value();
+                let low = Parse::evaluate(row, collations, low)?.into_value();
+                let high = Parse::evaluate(row, collations, high)?.into_value();
+
+                let above_low = value
+                    .as_ref()
+                    .zip(low)
+                    .map(|(v, l)| compare(&collation, v, &l) != Ordering::Less);
+                let below_high = value
+                    .as_ref()
+                    .zip(high)
+                    .map(|(v, h)| compare(&collation, v, &h) != Ordering::Greater);
+
+                let result = match (above_low, below_high) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                };
+
+                result.map(|b| b != *negated)
+            }
+            Expr::Like {
+                negated,
+                any: false,
+                expr,
+                pattern,
+                escape_char,
+            } => {
+                let collation = collation_of(collations, &[expr, pattern]);
+                let value = Parse::evaluate(row, collations, expr)?.into_value();
+                let pattern = Parse::evaluate(row, collations, pattern)?.into_value();
+
+                let escape = match escape_char.as_deref() {
+                    None => '\\',
+                    Some(escape) => escape.chars().next().ok_or("Incorrect ESCAPE character")?,
+                };
+
+                value
+                    .zip(pattern)
+                    .map(|(v, p)| {
+                        like(
+                            &collation,
+                            &v.chars().collect::<Vec<_>>(),
+                            &p.chars().collect::<Vec<_>>(),
+                            escape,
+                        )
+                    })
+                    .map(|b| b != *negated)
+            }
+            _ => return Err("Unsupported expression"),
+        };
+
+        Ok(from_truth(result))
+    }
+}
+
+impl Parser for EvaluateCase {
+    fn evaluate(
+        row: &HashMap<String, Option<String>>,
+        collations: &Collations,
+        expr: &Expr,
+    ) -> Result<ParseResult, &'static str> {
+        let (operand, conditions, else_result) = match expr {
+            Expr::Case {
+                operand,
+                conditions,
+                else_result,
+            } => (operand, conditions, else_result),
+            _ => return Err("Unsupported expression"),
+        };
+
+        let collation = match operand {
+            Some(operand) => collation_of(collations, &[operand]),
+            None => Collation::default(),
+        };
+
+        let operand = match operand {
+            Some(operand) => Some(Parse::evaluate(row, collations, operand)?.into_value()),
+            None => None,
+        };
+
+        for case in conditions {
+            let matched = match &operand {
+                Some(operand) => {
+                    let value = Parse::evaluate(row, collations, &case.condition)?.into_value();
+                    matches!((operand, value), (Some(o), Some(v)) if compare(&collation, o, &v) == Ordering::Equal)
+                }
+                None => truth(Parse::evaluate(row, collations, &case.condition)?) == Some(true),
+            };
+
+            if matched {
+                return Parse::evaluate(row, collations, &case.result);
+            }
+        }
+
+        match else_result {
+            Some(else_result) => Parse::evaluate(row, collations, else_result),
+            None => Ok(ParseResult::String(None)),
+        }
+    }
+}
+
+impl Parser for EvaluateArithmetic {
+    fn evaluate(
+        row: &HashMap<String, Option<String>>,
+        collations: &Collations,
+        expr: &Expr,
+    ) -> Result<ParseResult, &'static str> {
+        match expr {
+            Expr::UnaryOp { op, expr } => {
+                let value = match Parse::evaluate(row, collations, expr)?.into_value() {
+                    Some(value) => Number::from(value.as_str()),
+                    None => return Ok(ParseResult::String(None)),
+                };
+
+                match op {
+                    UnaryOperator::Minus => Ok(ParseResult::String(Some(
+                        Number::Int(0).subtract(value).to_string(),
+                    ))),
+                    _ => Ok(ParseResult::String(Some(value.to_string()))),
                 }
-                _ => panic!(),
+            }
+            Expr::BinaryOp { left, op, right } => {
+                let parsed_left = Parse::evaluate(row, collations, left)?.into_value();
+                let parsed_right = Parse::evaluate(row, collations, right)?.into_value();
+
+                let (l, r) = match (parsed_left, parsed_right) {
+                    (Some(l), Some(r)) => (l, r),
+                    _ => return Ok(ParseResult::String(None)),
+                };
+
+                let scale = decimal_places(&l);
+                let (l, r) = (Number::from(l.as_str()), Number::from(r.as_str()));
+
+                let result = match op {
+                    BinaryOperator::Plus => Some(l.add(r).to_string()),
+                    BinaryOperator::Minus => Some(l.subtract(r).to_string()),
+                    BinaryOperator::Multiply => Some(l.multiply(r).to_string()),
+                    // Division yields a decimal with 4 more digits than the dividend, as per div_precision_increment.
+                    BinaryOperator::Divide => match r.as_f64() == 0.0 {
+                        true => None,
+                        false => Some(format!("{:.*}", scale + 4, l.as_f64() / r.as_f64())),
+                    },
+                    BinaryOperator::MyIntegerDivide => match r.as_f64() == 0.0 {
+                        true => None,
+                        false => Some(((l.as_f64() / r.as_f64()).trunc() as i64).to_string()),
+                    },
+                    BinaryOperator::Modulo => match (l, r) {
+                        (_, Number::Int(0)) => None,
+                        (Number::Int(l), Number::Int(r)) => Some((l % r).to_string()),
+                        (l, r) => match r.as_f64() == 0.0 {
+                            true => None,
+                            false => Some(Number::Float(l.as_f64() % r.as_f64()).to_string()),
+                        },
+                    },
+                    _ => return Err("Unsupported arithmetic operator"),
+                };
+
+                Ok(ParseResult::String(result))
+            }
+            _ => Err("Unsupported expression"),
+        }
+    }
+}
+
+impl Parser for EvaluateFunction {
+    fn evaluate(
+        row: &HashMap<String, Option<String>>,
+        collations: &Collations,
+        expr: &Expr,
+    ) -> Result<ParseResult, &'static str> {
+        let function = match expr {
+            Expr::Function(function) => function,
+            _ => return Err("Unsupported expression"),
+        };
+
+        let name = function_name(function).ok_or("Unsupported function")?;
+        let args = function_args(row, collations, function)?;
+
+        let value = match name.as_str() {
+            "NOW" | "CURRENT_TIMESTAMP" | "LOCALTIMESTAMP" | "SYSDATE" | "UTC_TIMESTAMP" => {
+                Some(format_datetime(unix_time()))
+            }
+            "CURDATE" | "CURRENT_DATE" | "UTC_DATE" => {
+                Some(format_datetime(unix_time())[..10].to_string())
+            }
+            "UNIX_TIMESTAMP" if args.is_empty() => Some(unix_time().to_string()),
+            "UUID" => Some(Uuid::new_v4().to_string()),
+            "CONCAT" => args
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .map(|v| v.concat()),
+            "CONCAT_WS" => match args.split_first() {
+                Some((Some(separator), values)) => Some(
+                    values
+                        .iter()
+                        .flatten()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(separator),
+                ),
+                _ => None,
+            },
+            "UPPER" | "UCASE" => single_arg(args)?.map(|v| v.to_uppercase()),
+            "LOWER" | "LCASE" => single_arg(args)?.map(|v| v.to_lowercase()),
+            "LENGTH" => single_arg(args)?.map(|v| v.len().to_string()),
+            "CHAR_LENGTH" => single_arg(args)?.map(|v| v.chars().count().to_string()),
+            "ABS" => single_arg(args)?.map(|v| match Number::from(v.as_str()) {
+                Number::Int(i) => Number::Int(i.abs()).to_string(),
+                Number::Float(f) => Number::Float(f.abs()).to_string(),
+            }),
+            "COALESCE" => args.into_iter().flatten().next(),
+            "IFNULL" => match <[Option<String>; 2]>::try_from(args) {
+                Ok([value, fallback]) => value.or(fallback),
+                Err(_) => return Err("Incorrect parameter count in the call to IFNULL"),
             },
-            _ => todo!(),
+            _ => return Err("Unsupported function"),
+        };
+
+        Ok(ParseResult::String(value))
+    }
+}
+
+/// Truth value of a result in a boolean context, `None` being SQL's UNKNOWN.
+fn truth(result: ParseResult) -> Option<bool> {
+    match result {
+        ParseResult::Boolean(b) => Some(b),
+        ParseResult::String(value) => value.map(|v| Number::from(v.as_str()).as_f64() != 0.0),
+        ParseResult::Json(value) => truth(ParseResult::String(Some(json::unquote(&value)))),
+    }
+}
+
+fn from_truth(value: Option<bool>) -> ParseResult {
+    match value {
+        Some(b) => ParseResult::Boolean(b),
+        None => ParseResult::String(None),
+    }
+}
+
+/// Compares two values numerically if both are numbers, or as strings under `collation` otherwise.
+fn compare(collation: &Collation, l: &str, r: &str) -> Ordering {
+    match (numeric(l), numeric(r)) {
+        (Some(l), Some(r)) => l.partial_cmp(&r).unwrap_or(Ordering::Equal),
+        _ => collation.compare(l, r),
+    }
+}
+
+/// Collation of a comparison, taken from the first column operand as columns take precedence over literals.
+fn collation_of(collations: &Collations, operands: &[&Expr]) -> Collation {
+    operands
+        .iter()
+        .find_map(|operand| match operand {
+            Expr::Identifier(identifier) => collations.get(&identifier.value),
+            Expr::CompoundIdentifier(identifiers) => collations.get(&identifiers.last()?.value),
+            _ => None,
+        })
+        .map(|id| Collation::from_id(*id))
+        .unwrap_or_default()
+}
+
+/// Value of a string holding a number, which comparisons treat as such.
+pub fn numeric(value: &str) -> Option<f64> {
+    let value = value.trim();
+
+    if value.is_empty() || !value.chars().all(|c| "0123456789+-.eE".contains(c)) {
+        return None;
+    }
+
+    value.parse::<f64>().ok()
+}
+
+/// Matches `value` against a LIKE pattern, where `%` matches any sequence and `_` any single character.
+fn like(collation: &Collation, value: &[char], pattern: &[char], escape: char) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some(('%', rest)) => (0..=value.len()).any(|i| like(collation, &value[i..], rest, escape)),
+        Some(('_', rest)) => !value.is_empty() && like(collation, &value[1..], rest, escape),
+        Some((c, rest)) => {
+            let (expected, rest) = match (*c == escape, rest.split_first()) {
+                (true, Some((escaped, rest))) => (escaped, rest),
+                _ => (c, rest),
+            };
+
+            value
+                .first()
+                .is_some_and(|v| collation.chars_equal(*v, *expected))
+                && like(collation, &value[1..], rest, escape)
         }
     }
 }
 
-impl Parser for EvaluateCondition {
+impl Parser for EvaluateJson {
     fn evaluate(
         row: &HashMap<String, Option<String>>,
-        expr: &Box<Expr>,
+        collations: &Collations,
+        expr: &Expr,
     ) -> Result<ParseResult, &'static str> {
-        if let Expr::BinaryOp { left, op, right } = &**expr {
-            let parsed_left = Parse::evaluate(row, left)?;
-            let parsed_right = Parse::evaluate(row, right)?;
-
-            match op {
-                BinaryOperator::Eq => {
-                    return match (parsed_left, parsed_right) {
-                        (ParseResult::Boolean(l), ParseResult::Boolean(r)) => {
-                            Ok(ParseResult::Boolean(l == r))
-                        }
-                        (ParseResult::String(l), ParseResult::String(r)) => {
-                            Ok(ParseResult::Boolean(l == r))
-                        }
-                        _ => Ok(ParseResult::Boolean(false)),
-                    };
+        let (name, args): (String, Vec<&Expr>) = match expr {
+            Expr::BinaryOp {
+                left,
+                op: op @ (BinaryOperator::Arrow | BinaryOperator::LongArrow),
+                right,
+            } if matches!(
+                **right,
+                Expr::BinaryOp { .. }
+                    | Expr::IsNull(_)
+                    | Expr::IsNotNull(_)
+                    | Expr::InList { .. }
+                    | Expr::Between { .. }
+                    | Expr::Like { .. }
+            ) =>
+            {
+                return Parse::evaluate(row, collations, &rebind_json_operator(left, op, right));
+            }
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::Arrow,
+                right,
+            } => ("JSON_EXTRACT".to_string(), vec![left, right]),
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::LongArrow,
+                right,
+            } => {
+                let extracted = Parse::evaluate(
+                    row,
+                    collations,
+                    &Expr::BinaryOp {
+                        left: left.clone(),
+                        op: BinaryOperator::Arrow,
+                        right: right.clone(),
+                    },
+                )?;
+
+                return Ok(match extracted {
+                    ParseResult::Json(value) => ParseResult::String(Some(json::unquote(&value))),
+                    result => result,
+                });
+            }
+            Expr::Function(function) => (
+                function_name(function).ok_or("Unsupported function")?,
+                function_arg_exprs(function)?,
+            ),
+            _ => return Err("Unsupported expression"),
+        };
+
+        let document = match args.first() {
+            Some(arg) => match as_document(Parse::evaluate(row, collations, arg)?)? {
+                Some(document) => document,
+                None => return Ok(ParseResult::String(None)),
+            },
+            None => return Err("Incorrect parameter count in the call to native function"),
+        };
+
+        let mut paths = vec![];
+        let mut values = vec![];
+
+        // Mutation functions take (path, value) pairs, JSON_CONTAINS a candidate and the others a list of paths.
+        let pairs = matches!(name.as_str(), "JSON_SET" | "JSON_INSERT" | "JSON_REPLACE");
+        let candidate = name == "JSON_CONTAINS";
+
+        for (i, arg) in args.iter().enumerate().skip(1) {
+            let result = Parse::evaluate(row, collations, arg)?;
+
+            if (pairs && i % 2 == 0) || (candidate && i == 1) {
+                values.push(match candidate {
+                    true => match as_document(result)? {
+                        Some(candidate) => candidate,
+                        None => return Ok(ParseResult::String(None)),
+                    },
+                    false => as_json_value(result, arg),
+                });
+                continue;
+            }
+
+            match result.into_value() {
+                Some(path) => paths.push(json::parse_path(&path)?),
+                None => return Ok(ParseResult::String(None)),
+            }
+        }
+
+        match name.as_str() {
+            "JSON_EXTRACT" => {
+                let mut extracted: Vec<serde_json::Value> = paths
+                    .iter()
+                    .filter_map(|path| json::extract(&document, path).cloned())
+                    .collect();
+
+                Ok(match (paths.len(), extracted.len()) {
+                    (_, 0) => ParseResult::String(None),
+                    (1, _) => ParseResult::Json(extracted.remove(0)),
+                    _ => ParseResult::Json(serde_json::Value::Array(extracted)),
+                })
+            }
+            "JSON_UNQUOTE" => Ok(ParseResult::String(Some(json::unquote(&document)))),
+            "JSON_SET" | "JSON_INSERT" | "JSON_REPLACE" => {
+                if paths.len() != values.len() {
+                    return Err("Incorrect parameter count in the call to native function");
                 }
-                BinaryOperator::NotEq => {
-                    return match (parsed_left, parsed_right) {
-                        (ParseResult::Boolean(l), ParseResult::Boolean(r)) => {
-                            Ok(ParseResult::Boolean(l != r))
-                        }
-                        (ParseResult::String(l), ParseResult::String(r)) => {
-                            Ok(ParseResult::Boolean(l != r))
-                        }
-                        _ => Ok(ParseResult::Boolean(true)),
-                    };
+
+                let mut document = document;
+                for (path, value) in paths.iter().zip(values) {
+                    json::set(
+                        &mut document,
+                        path,
+                        value,
+                        name != "JSON_REPLACE",
+                        name != "JSON_INSERT",
+                    );
                 }
-                BinaryOperator::Lt
-                | BinaryOperator::LtEq
-                | BinaryOperator::Gt
-                | BinaryOperator::GtEq => {
-                    return match (parsed_left, parsed_right) {
-                        (ParseResult::String(l), ParseResult::String(r)) => {
-                            if l.is_none() || r.is_none() {
-                                return Ok(ParseResult::Boolean(false));
-                            }
 
-                            match op {
-                                BinaryOperator::Lt => {
-                                    Ok(ParseResult::Boolean(l.unwrap() < r.unwrap()))
-                                }
-                                BinaryOperator::LtEq => {
-                                    Ok(ParseResult::Boolean(l.unwrap() <= r.unwrap()))
-                                }
-                                BinaryOperator::Gt => {
-                                    Ok(ParseResult::Boolean(l.unwrap() > r.unwrap()))
-                                }
-                                BinaryOperator::GtEq => {
-                                    Ok(ParseResult::Boolean(l.unwrap() >= r.unwrap()))
-                                }
-                                _ => unreachable!(),
-                            }
-                        }
+                Ok(ParseResult::Json(document))
+            }
+            "JSON_REMOVE" => {
+                let mut document = document;
+                for path in &paths {
+                    json::remove(&mut document, path);
+                }
 
-                        (_, _) => Ok(ParseResult::Boolean(false)),
-                    }
+                Ok(ParseResult::Json(document))
+            }
+            "JSON_CONTAINS" => {
+                let candidate = match values.pop() {
+                    Some(candidate) => candidate,
+                    None => return Err("Incorrect parameter count in the call to native function"),
+                };
+
+                let target = match paths.first() {
+                    Some(path) => json::extract(&document, path),
+                    None => Some(&document),
+                };
+
+                Ok(from_truth(
+                    target.map(|target| json::contains(target, &candidate)),
+                ))
+            }
+            _ => Err("Unsupported function"),
+        }
+    }
+}
+
+/// The parser gives `->` and `->>` a lower precedence than comparison and arithmetic operators, so that
+/// `col->'$.a' = 1` is parsed as `col -> ('$.a' = 1)`. As the path must be a literal, the operator is moved back
+/// onto the leftmost operand of the expression it captured, i.e, `(col->'$.a') = 1`.
+fn rebind_json_operator(left: &Expr, op: &BinaryOperator, right: &Expr) -> Expr {
+    let rebind = |operand: &Expr| Box::new(rebind_json_operator(left, op, operand));
+
+    match right {
+        Expr::BinaryOp {
+            left: inner_left,
+            op: inner_op,
+            right: inner_right,
+        } => Expr::BinaryOp {
+            left: rebind(inner_left),
+            op: inner_op.clone(),
+            right: inner_right.clone(),
+        },
+        Expr::IsNull(expr) => Expr::IsNull(rebind(expr)),
+        Expr::IsNotNull(expr) => Expr::IsNotNull(rebind(expr)),
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => Expr::InList {
+            expr: rebind(expr),
+            list: list.clone(),
+            negated: *negated,
+        },
+        Expr::Between {
+            expr,
+            negated,
+            low,
+            high,
+        } => Expr::Between {
+            expr: rebind(expr),
+            negated: *negated,
+            low: low.clone(),
+            high: high.clone(),
+        },
+        Expr::Like {
+            negated,
+            any,
+            expr,
+            pattern,
+            escape_char,
+        } => Expr::Like {
+            negated: *negated,
+            any: *any,
+            expr: rebind(expr),
+            pattern: pattern.clone(),
+            escape_char: escape_char.clone(),
+        },
+        _ => Expr::BinaryOp {
+            left: Box::new(left.clone()),
+            op: op.clone(),
+            right: Box::new(right.clone()),
+        },
+    }
+}
+
+/// Parses a JSON document argument, SQL NULL yielding `None`.
+fn as_document(result: ParseResult) -> Result<Option<serde_json::Value>, &'static str> {
+    match result {
+        ParseResult::Json(value) => Ok(Some(value)),
+        ParseResult::Boolean(b) => Ok(Some(serde_json::Value::Bool(b))),
+        ParseResult::String(None) => Ok(None),
+        ParseResult::String(Some(text)) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|_| "Invalid JSON text"),
+    }
+}
+
+/// Converts an SQL value into a JSON value, string literals becoming JSON strings and numbers JSON numbers.
+fn as_json_value(result: ParseResult, expr: &Expr) -> serde_json::Value {
+    match result {
+        ParseResult::Json(value) => value,
+        ParseResult::Boolean(b) => serde_json::Value::Bool(b),
+        ParseResult::String(None) => serde_json::Value::Null,
+        ParseResult::String(Some(text)) => match expr {
+            Expr::Value(value) if value.clone().into_string().is_some() => {
+                serde_json::Value::String(text)
+            }
+            _ => match numeric(&text) {
+                Some(_) => serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)),
+                None => serde_json::Value::String(text),
+            },
+        },
+    }
+}
+
+/// Compares a JSON value with another value, the non-JSON side being converted to JSON first.
+fn compare_json(
+    op: &BinaryOperator,
+    (left, left_expr): (ParseResult, &Expr),
+    (right, right_expr): (ParseResult, &Expr),
+) -> Result<ParseResult, &'static str> {
+    let (l, r) = match (left, right) {
+        (ParseResult::String(None), right) => {
+            return Ok(match op {
+                BinaryOperator::Spaceship => {
+                    ParseResult::Boolean(matches!(right, ParseResult::String(None)))
                 }
-                _ => panic!(),
+                _ => ParseResult::String(None),
+            });
+        }
+        (_, ParseResult::String(None)) => {
+            return Ok(match op {
+                BinaryOperator::Spaceship => ParseResult::Boolean(false),
+                _ => ParseResult::String(None),
+            });
+        }
+        (left, right) => (
+            as_json_value(left, left_expr),
+            as_json_value(right, right_expr),
+        ),
+    };
+
+    let ordering = json::compare(&l, &r);
+
+    let result = match op {
+        BinaryOperator::Eq | BinaryOperator::Spaceship => ordering == Some(Ordering::Equal),
+        BinaryOperator::NotEq => ordering != Some(Ordering::Equal),
+        BinaryOperator::Lt => ordering == Some(Ordering::Less),
+        BinaryOperator::LtEq => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        BinaryOperator::Gt => ordering == Some(Ordering::Greater),
+        BinaryOperator::GtEq => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        _ => return Err("Unsupported operator"),
+    };
+
+    Ok(ParseResult::Boolean(result))
+}
+
+fn function_name(function: &Function) -> Option<String> {
+    Some(function.name.0.last()?.as_ident()?.value.to_uppercase())
+}
+
+fn function_arg_exprs(function: &Function) -> Result<Vec<&Expr>, &'static str> {
+    match &function.args {
+        FunctionArguments::None => Ok(vec![]),
+        FunctionArguments::Subquery(_) => Err("Subqueries are not supported"),
+        FunctionArguments::List(list) => list
+            .args
+            .iter()
+            .map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Ok(expr),
+                _ => Err("Unsupported function argument"),
+            })
+            .collect(),
+    }
+}
+
+fn function_args(
+    row: &HashMap<String, Option<String>>,
+    collations: &Collations,
+    function: &Function,
+) -> Result<Vec<Option<String>>, &'static str> {
+    let list = match &function.args {
+        FunctionArguments::None => return Ok(vec![]),
+        FunctionArguments::Subquery(_) => return Err("Subqueries are not supported"),
+        FunctionArguments::List(list) => list,
+    };
+
+    list.args
+        .iter()
+        .map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => {
+                Ok(Parse::evaluate(row, collations, expr)?.into_value())
             }
+            _ => Err("Unsupported function argument"),
+        })
+        .collect()
+}
+
+fn single_arg(args: Vec<Option<String>>) -> Result<Option<String>, &'static str> {
+    match <[Option<String>; 1]>::try_from(args) {
+        Ok([value]) => Ok(value),
+        Err(_) => Err("Incorrect parameter count in the call to native function"),
+    }
+}
+
+/// Numeric value of a string, following MySQL's implicit conversion of using the longest numeric prefix.
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl From<&str> for Number {
+    fn from(value: &str) -> Self {
+        let value = value.trim();
+
+        if let Ok(i) = value.parse::<i64>() {
+            return Number::Int(i);
+        }
+        if let Ok(f) = value.parse::<f64>() {
+            return Number::Float(f);
+        }
+
+        let prefix_length = value
+            .char_indices()
+            .take_while(|(i, c)| c.is_ascii_digit() || *c == '.' || (*i == 0 && "+-".contains(*c)))
+            .count();
+
+        (1..=prefix_length)
+            .rev()
+            .find_map(|length| match &value[..length] {
+                prefix if !prefix.contains('.') => prefix.parse::<i64>().ok().map(Number::Int),
+                prefix => prefix.parse::<f64>().ok().map(Number::Float),
+            })
+            .unwrap_or(Number::Int(0))
+    }
+}
+
+impl Number {
+    fn as_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::Float(f) => *f,
+        }
+    }
+
+    fn add(self, other: Number) -> Number {
+        match (self, other) {
+            (Number::Int(l), Number::Int(r)) if l.checked_add(r).is_some() => Number::Int(l + r),
+            (l, r) => Number::Float(l.as_f64() + r.as_f64()),
+        }
+    }
+
+    fn subtract(self, other: Number) -> Number {
+        match (self, other) {
+            (Number::Int(l), Number::Int(r)) if l.checked_sub(r).is_some() => Number::Int(l - r),
+            (l, r) => Number::Float(l.as_f64() - r.as_f64()),
         }
+    }
 
-        unreachable!();
+    fn multiply(self, other: Number) -> Number {
+        match (self, other) {
+            (Number::Int(l), Number::Int(r)) if l.checked_mul(r).is_some() => Number::Int(l * r),
+            (l, r) => Number::Float(l.as_f64() * r.as_f64()),
+        }
     }
 }
 
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(i) => write!(f, "{}", i),
+            Number::Float(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+fn decimal_places(value: &str) -> usize {
+    value
+        .trim()
+        .split_once('.')
+        .map(|(_, fraction)| fraction.chars().take_while(|c| c.is_ascii_digit()).count())
+        .unwrap_or(0)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats seconds since the epoch as a UTC DATETIME, i.e, `YYYY-MM-DD hh:mm:ss`.
+fn format_datetime(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+
+    // Civil date from days since epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::materialization::evaluator::{Parse, Parser};
+    use crate::materialization::collation::Collations;
+    use crate::materialization::evaluator::{Parse, ParseResult, Parser};
     use log::debug;
     use sqlparser::ast::Statement;
     use sqlparser::dialect::MySqlDialect;
@@ -245,7 +1098,183 @@ mod tests {
 
         if let Statement::Update { selection, .. } = parsed_sql.first().unwrap() {
             let expr = selection.clone().unwrap();
-            debug!("{:?}", Parse::evaluate(&row, &Box::from(expr)));
+            debug!(
+                "{:?}",
+                Parse::evaluate(&row, &Collations::new(), &Box::from(expr))
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_truthiness() {
+        let cases = [
+            (ParseResult::Boolean(true), true),
+            (ParseResult::Boolean(false), false),
+            (ParseResult::String(None), false),
+            (ParseResult::String(Some(String::from("2"))), true),
+            (ParseResult::String(Some(String::from("0.0"))), false),
+            (ParseResult::String(Some(String::from("ACTIVE"))), false),
+        ];
+
+        for (result, expected) in cases {
+            assert_eq!(expected, result.is_true(), "{:?}", result);
+        }
+    }
+
+    #[test]
+    pub fn test_arithmetic_and_functions() {
+        let row = HashMap::from([
+            (String::from("a"), Some(String::from("7"))),
+            (String::from("b"), Some(String::from("2.5"))),
+            (String::from("x"), None),
+        ]);
+
+        let cases = [
+            ("a + 1", Some("8")),
+            ("a - b", Some("4.5")),
+            ("-a * 2", Some("-14")),
+            ("a / 2", Some("3.5000")),
+            ("a DIV 2", Some("3")),
+            ("a % 4", Some("3")),
+            ("a / 0", None),
+            ("x + 1", None),
+            ("concat('id-', a)", Some("id-7")),
+            ("concat('id-', x)", None),
+            ("upper('abc')", Some("ABC")),
+            ("ifnull(x, 'none')", Some("none")),
+        ];
+
+        for (expr, expected) in cases {
+            let parsed = sqlparser::parser::Parser::new(&MySqlDialect {})
+                .try_with_sql(expr)
+                .unwrap()
+                .parse_expr()
+                .unwrap();
+
+            let result = Parse::evaluate(&row, &Collations::new(), &parsed)
+                .unwrap()
+                .into_value();
+            assert_eq!(expected.map(String::from), result, "{}", expr);
+        }
+
+        let now = Parse::evaluate(
+            &row,
+            &Collations::new(),
+            &sqlparser::parser::Parser::new(&MySqlDialect {})
+                .try_with_sql("NOW()")
+                .unwrap()
+                .parse_expr()
+                .unwrap(),
+        )
+        .unwrap()
+        .into_value()
+        .unwrap();
+        assert_eq!(19, now.len());
+    }
+
+    #[test]
+    pub fn test_predicates() {
+        let row = HashMap::from([
+            (String::from("id"), Some(String::from("10"))),
+            (String::from("status"), Some(String::from("ACTIVE"))),
+            (String::from("x"), None),
+        ]);
+
+        let cases = [
+            ("id IN (1, 10)", Some("1")),
+            ("id NOT IN (1, 2)", Some("1")),
+            ("id IN (1, NULL)", None),
+            ("id BETWEEN 9 AND 11", Some("1")),
+            ("id > 9", Some("1")),
+            ("status LIKE 'ACT%'", Some("1")),
+            ("status LIKE 'A_TIVE'", Some("1")),
+            ("status NOT LIKE '%IVE'", Some("0")),
+            ("'50%' LIKE '50\\%'", Some("1")),
+            ("NOT (id = 10)", Some("0")),
+            ("x = 1", None),
+            ("NOT x = 1", None),
+            ("x = 1 AND id = 1", Some("0")),
+            ("x = 1 OR id = 10", Some("1")),
+            ("x = 1 OR id = 1", None),
+            ("x <=> NULL", Some("1")),
+            ("CASE WHEN id > 5 THEN 'big' ELSE 'small' END", Some("big")),
+            ("CASE status WHEN 'NEW' THEN 1 END", None),
+            ("COALESCE(x, NULL, status)", Some("ACTIVE")),
+        ];
+
+        for (expr, expected) in cases {
+            let parsed = sqlparser::parser::Parser::new(&MySqlDialect {})
+                .try_with_sql(expr)
+                .unwrap()
+                .parse_expr()
+                .unwrap();
+
+            let result = Parse::evaluate(&row, &Collations::new(), &parsed)
+                .unwrap()
+                .into_value();
+            assert_eq!(expected.map(String::from), result, "{}", expr);
+        }
+    }
+
+    #[test]
+    pub fn test_collation_aware_comparison() {
+        let row = HashMap::from([(String::from("status"), Some(String::from("ACTIVE")))]);
+        let parsed = sqlparser::parser::Parser::new(&MySqlDialect {})
+            .try_with_sql("status = 'active' AND status LIKE 'act%'")
+            .unwrap()
+            .parse_expr()
+            .unwrap();
+
+        let ai_ci = Collations::from([(String::from("status"), 255)]);
+        let bin = Collations::from([(String::from("status"), 46)]);
+
+        assert!(matches!(
+            Parse::evaluate(&row, &ai_ci, &parsed),
+            Ok(ParseResult::Boolean(true))
+        ));
+        assert!(matches!(
+            Parse::evaluate(&row, &bin, &parsed),
+            Ok(ParseResult::Boolean(false))
+        ));
+    }
+
+    #[test]
+    pub fn test_json_expressions() {
+        let row = HashMap::from([(
+            String::from("attributes"),
+            Some(String::from(
+                r#"{"plan": "pro", "seats": 5, "tags": ["beta"]}"#,
+            )),
+        )]);
+
+        let cases = [
+            ("attributes->>'$.plan' = 'pro'", Some("1")),
+            ("attributes->'$.plan' = 'pro'", Some("1")),
+            ("attributes->'$.seats' > 3", Some("1")),
+            ("attributes->'$.missing' IS NULL", Some("1")),
+            ("JSON_EXTRACT(attributes, '$.tags[0]')", Some(r#""beta""#)),
+            ("JSON_CONTAINS(attributes, '\"beta\"', '$.tags')", Some("1")),
+            (
+                "JSON_SET(attributes, '$.plan', 'free', '$.seats', attributes->'$.seats' + 1)",
+                Some(r#"{"plan": "free", "tags": ["beta"], "seats": 6}"#),
+            ),
+            (
+                "JSON_REMOVE(attributes, '$.tags')",
+                Some(r#"{"plan": "pro", "seats": 5}"#),
+            ),
+        ];
+
+        for (expr, expected) in cases {
+            let parsed = sqlparser::parser::Parser::new(&MySqlDialect {})
+                .try_with_sql(expr)
+                .unwrap()
+                .parse_expr()
+                .unwrap();
+
+            let result = Parse::evaluate(&row, &Collations::new(), &parsed)
+                .unwrap()
+                .into_value();
+            assert_eq!(expected.map(String::from), result, "{}", expr);
         }
     }
 }
@@ -0,0 +1,172 @@
+use crate::materialization::{ordered_divergences, Divergence, StateDiffLog};
+use sqlparser::ast::{Ident, ObjectName, Value};
+
+/// Makes string literals with backslashes read as they were written, as they are rendered without escaping them.
+const SQL_MODE: &str =
+    "SET SESSION sql_mode = CONCAT(@@SESSION.sql_mode, ',NO_BACKSLASH_ESCAPES');";
+
+/// Renders the divergences of the diff log as SQL statements, in the order they were recorded, so that intercepted
+/// writes can be applied to a real database. The statements are preceded by the one setting the SQL mode they have to
+/// be run with, unless there are none.
+pub fn export_sql(diff: &StateDiffLog) -> Vec<String> {
+    let statements: Vec<String> = diff_statements(diff, None)
+        .into_iter()
+        .map(|(_, _, statement)| statement)
+        .collect();
+
+    match statements.is_empty() {
+        true => statements,
+        false => [SQL_MODE.to_string()]
+            .into_iter()
+            .chain(statements)
+            .collect(),
+    }
+}
+
+/// (sequence number, table key, statement) of each divergence of the diff log, optionally limited to one table, in
+/// the order they were recorded.
+pub fn diff_statements(diff: &StateDiffLog, table_key: Option<&str>) -> Vec<(u64, String, String)> {
+    let mut statements = vec![];
+
+    for mut entry in diff.iter_mut() {
+        if table_key.is_some_and(|table_key| table_key != entry.key()) {
+            continue;
+        }
+
+        let key = entry.key().clone();
+        let table = quoted_table_name(&key);
+        statements.extend(ordered_divergences(entry.value_mut()).into_iter().map(
+            |(sequence, divergence)| (sequence, key.clone(), to_statement(&table, &divergence)),
+        ));
+    }
+
+    statements.sort_by_key(|(sequence, _, _)| *sequence);
+    statements
+}
+
+/// Complete migration script, running the exported statements in a single transaction.
+pub fn export_script(diff: &StateDiffLog) -> String {
+    let statements: Vec<String> = diff_statements(diff, None)
+        .into_iter()
+        .map(|(_, _, statement)| statement)
+        .collect();
+
+    let mut script = vec![
+        format!("-- {} statements exported by MocksySQL", statements.len()),
+        SQL_MODE.to_string(),
+        "START TRANSACTION;".to_string(),
+    ];
+    script.extend(statements);
+    script.push("COMMIT;".to_string());
+
+    script.join("\n") + "\n"
+}
+
+fn to_statement(table: &ObjectName, divergence: &Divergence) -> String {
+    let where_clause = |selection: &Option<Box<_>>| match selection {
+        Some(selection) => format!(" WHERE {}", selection),
+        None => String::new(),
+    };
+
+    match divergence {
+        Divergence::Update(selection, assignments) => format!(
+            "UPDATE {} SET {}{};",
+            table,
+            assignments
+                .iter()
+                .map(|(column, expr)| format!("{} = {}", quoted(column), expr))
+                .collect::<Vec<_>>()
+                .join(", "),
+            where_clause(selection)
+        ),
+        Divergence::Patch(row_key, assignments) => format!(
+            "UPDATE {} SET {} WHERE {};",
+            table,
+            assignments
+                .iter()
+                .map(|(column, expr)| format!("{} = {}", quoted(column), expr))
+                .collect::<Vec<_>>()
+                .join(", "),
+            row_key
+                .iter()
+                .map(|(column, value)| format!(
+                    "{} = {}",
+                    quoted(column),
+                    Value::SingleQuotedString(value.to_string())
+                ))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        ),
+        Divergence::Delete(selection) => {
+            format!("DELETE FROM {}{};", table, where_clause(selection))
+        }
+        Divergence::Insert(row, _) => {
+            // Sorted so that the output is stable.
+            let mut columns: Vec<_> = row.iter().collect();
+            columns.sort_by_key(|(column, _)| *column);
+
+            format!(
+                "INSERT INTO {} ({}) VALUES ({});",
+                table,
+                columns
+                    .iter()
+                    .map(|(column, _)| quoted(column).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                columns
+                    .iter()
+                    .map(|(_, value)| match value {
+                        Some(value) => Value::SingleQuotedString(value.to_string()).to_string(),
+                        None => "NULL".to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    }
+}
+
+fn quoted_table_name(table_key: &str) -> ObjectName {
+    ObjectName::from(table_key.split('.').map(quoted).collect::<Vec<_>>())
+}
+
+fn quoted(identifier: &str) -> Ident {
+    Ident::with_quote('`', identifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::materialization::export::export_sql;
+    use crate::materialization::{get_diff, StateDiffLog, TableStore};
+    use crate::mysql::command::{Command, MySqlCommand};
+
+    #[test]
+    fn test_export_sql() {
+        let mut diff = StateDiffLog::default();
+
+        for query in [
+            "insert into account (id, status) values (1, 'it''s'), (2, NULL)",
+            "update account set balance = balance + 1, status = 'A' where id = 1",
+            "delete from shop.orders where account_id = 2",
+        ] {
+            get_diff(
+                &mut diff,
+                &TableStore::default(),
+                Some("shop"),
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        assert!(export_sql(&StateDiffLog::default()).is_empty());
+        assert_eq!(
+            vec![
+                "SET SESSION sql_mode = CONCAT(@@SESSION.sql_mode, ',NO_BACKSLASH_ESCAPES');",
+                "INSERT INTO `shop`.`account` (`id`, `status`) VALUES ('1', 'it''s');",
+                "INSERT INTO `shop`.`account` (`id`, `status`) VALUES ('2', NULL);",
+                "UPDATE `shop`.`account` SET `balance` = balance + 1, `status` = 'A' WHERE id = 1;",
+                "DELETE FROM `shop`.`orders` WHERE account_id = 2;",
+            ],
+            export_sql(&diff)
+        );
+    }
+}
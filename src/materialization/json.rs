@@ -0,0 +1,273 @@
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// Single step of a JSON path, e.g, `.name` or `[2]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathLeg {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses a MySQL JSON path such as `$.attributes."first name"[0]`. Wildcards and ranges are not supported.
+pub fn parse_path(path: &str) -> Result<Vec<PathLeg>, &'static str> {
+    let mut chars = path.trim().chars().peekable();
+    let mut legs = vec![];
+
+    if chars.next() != Some('$') {
+        return Err("Invalid JSON path expression");
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut key = String::new();
+
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => key.extend(chars.next()),
+                            Some(c) => key.push(c),
+                            None => return Err("Invalid JSON path expression"),
+                        }
+                    }
+                } else {
+                    while let Some(c) = chars.next_if(|c| !matches!(c, '.' | '[')) {
+                        key.push(c);
+                    }
+                }
+
+                if key.is_empty() || key == "*" {
+                    return Err("Unsupported JSON path expression");
+                }
+                legs.push(PathLeg::Key(key));
+            }
+            '[' => {
+                let mut index = String::new();
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    index.push(c);
+                }
+
+                let index = index
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| "Unsupported JSON path expression")?;
+                legs.push(PathLeg::Index(index));
+            }
+            c if c.is_whitespace() => {}
+            _ => return Err("Invalid JSON path expression"),
+        }
+    }
+
+    Ok(legs)
+}
+
+pub fn extract<'a>(document: &'a Value, path: &[PathLeg]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(document, |value, leg| match (leg, value) {
+            (PathLeg::Key(key), Value::Object(object)) => object.get(key),
+            (PathLeg::Index(index), Value::Array(array)) => array.get(*index),
+            // A scalar is treated as a single element array.
+            (PathLeg::Index(0), value) => Some(value),
+            _ => None,
+        })
+}
+
+/// Sets the value at `path`, as done by JSON_SET (`insert` and `replace`), JSON_INSERT (`insert` only) and
+/// JSON_REPLACE (`replace` only). Missing intermediate members are not created.
+pub fn set(document: &mut Value, path: &[PathLeg], new_value: Value, insert: bool, replace: bool) {
+    let (last, parents) = match path.split_last() {
+        Some(split) => split,
+        None => {
+            if replace {
+                *document = new_value;
+            }
+            return;
+        }
+    };
+
+    let parent = match extract_mut(document, parents) {
+        Some(parent) => parent,
+        None => return,
+    };
+
+    match (last, parent) {
+        (PathLeg::Key(key), Value::Object(object)) => {
+            let exists = object.contains_key(key);
+            if (exists && replace) || (!exists && insert) {
+                object.insert(key.clone(), new_value);
+            }
+        }
+        (PathLeg::Index(index), Value::Array(array)) => {
+            if *index < array.len() {
+                if replace {
+                    array[*index] = new_value;
+                }
+            } else if insert {
+                array.push(new_value);
+            }
+        }
+        (PathLeg::Index(index), parent) => {
+            if *index == 0 {
+                if replace {
+                    *parent = new_value;
+                }
+            } else if insert {
+                let existing = parent.take();
+                *parent = Value::Array(vec![existing, new_value]);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn remove(document: &mut Value, path: &[PathLeg]) {
+    let (last, parents) = match path.split_last() {
+        Some(split) => split,
+        None => return,
+    };
+
+    match (last, extract_mut(document, parents)) {
+        (PathLeg::Key(key), Some(Value::Object(object))) => {
+            object.remove(key);
+        }
+        (PathLeg::Index(index), Some(Value::Array(array))) if *index < array.len() => {
+            array.remove(*index);
+        }
+        _ => {}
+    }
+}
+
+fn extract_mut<'a>(document: &'a mut Value, path: &[PathLeg]) -> Option<&'a mut Value> {
+    path.iter()
+        .try_fold(document, |value, leg| match (leg, value) {
+            (PathLeg::Key(key), Value::Object(object)) => object.get_mut(key),
+            (PathLeg::Index(index), Value::Array(array)) => array.get_mut(*index),
+            (PathLeg::Index(0), value) => Some(value),
+            _ => None,
+        })
+}
+
+/// JSON_CONTAINS semantics: scalars must be equal, arrays contain every element of the candidate,
+/// objects contain every member of the candidate.
+pub fn contains(target: &Value, candidate: &Value) -> bool {
+    match (target, candidate) {
+        (Value::Array(target), Value::Array(candidate)) => candidate
+            .iter()
+            .all(|c| target.iter().any(|t| contains(t, c))),
+        (Value::Array(target), candidate) => target.iter().any(|t| contains(t, candidate)),
+        (Value::Object(target), Value::Object(candidate)) => candidate
+            .iter()
+            .all(|(key, c)| target.get(key).is_some_and(|t| contains(t, c))),
+        (target, candidate) => compare(target, candidate) == Some(Ordering::Equal),
+    }
+}
+
+/// Compares JSON values of the same type, numbers being compared by value. Values of different types never match.
+pub fn compare(l: &Value, r: &Value) -> Option<Ordering> {
+    match (l, r) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64()?.partial_cmp(&r.as_f64()?),
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        (Value::Bool(l), Value::Bool(r)) => Some(l.cmp(r)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Array(_), Value::Array(_)) | (Value::Object(_), Value::Object(_)) => {
+            (l == r).then_some(Ordering::Equal)
+        }
+        _ => None,
+    }
+}
+
+/// Serializes a value the way MySQL prints JSON, i.e, `{"a": 1, "b": [1, 2]}`, with object keys ordered by
+/// length and then lexicographically.
+pub fn to_mysql_string(value: &Value) -> String {
+    match value {
+        Value::Array(array) => format!(
+            "[{}]",
+            array
+                .iter()
+                .map(to_mysql_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Object(object) => format!(
+            "{{{}}}",
+            sorted_members(object)
+                .into_iter()
+                .map(|(key, value)| format!(
+                    "{}: {}",
+                    Value::from(key.clone()),
+                    to_mysql_string(value)
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        value => value.to_string(),
+    }
+}
+
+fn sorted_members(object: &Map<String, Value>) -> Vec<(&String, &Value)> {
+    let mut members: Vec<_> = object.iter().collect();
+    members.sort_by(|(l, _), (r, _)| l.len().cmp(&r.len()).then_with(|| l.cmp(r)));
+    members
+}
+
+/// Text of a value as returned by `->>` and JSON_UNQUOTE.
+pub fn unquote(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        value => to_mysql_string(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::materialization::json::{contains, parse_path, set, to_mysql_string, PathLeg};
+    use serde_json::json;
+
+    #[test]
+    fn test_json_paths() {
+        assert_eq!(
+            vec![
+                PathLeg::Key("attributes".to_string()),
+                PathLeg::Key("first name".to_string()),
+                PathLeg::Index(1)
+            ],
+            parse_path("$.attributes.\"first name\"[1]").unwrap()
+        );
+        assert!(parse_path("$.*").is_err());
+
+        let mut document = json!({"tags": ["a"], "plan": {"tier": "free"}});
+        set(
+            &mut document,
+            &parse_path("$.plan.tier").unwrap(),
+            json!("pro"),
+            true,
+            true,
+        );
+        set(
+            &mut document,
+            &parse_path("$.tags[5]").unwrap(),
+            json!("b"),
+            true,
+            true,
+        );
+        set(
+            &mut document,
+            &parse_path("$.missing.key").unwrap(),
+            json!(1),
+            true,
+            true,
+        );
+
+        assert_eq!(
+            r#"{"plan": {"tier": "pro"}, "tags": ["a", "b"]}"#,
+            to_mysql_string(&document)
+        );
+        assert!(contains(&document, &json!({"tags": ["b"]})));
+        assert!(!contains(&document, &json!({"plan": {"tier": "free"}})));
+    }
+}
@@ -0,0 +1,271 @@
+use crate::connection::Connection;
+use crate::materialization::{
+    literal_value, local_table_key, schema_name, table_key, unqualified_name, upstream,
+    Assignments, LocalTable, RowKey,
+};
+use log::{debug, warn};
+use sqlparser::ast::{BinaryOperator, Expr, Statement, TableFactor};
+use std::collections::HashMap;
+
+/// Makes sure every table targeted by an UPDATE in `ast` has its primary and unique keys known.
+/// Keys are learnt once per table from the upstream server, unless the table was defined by a seed file.
+pub fn prepare_keys(connection: &mut Connection, ast: &Option<Vec<Statement>>) {
+    let statements = match ast {
+        Some(statements) => statements,
+        None => return,
+    };
+
+    for statement in statements {
+        let name = match statement {
+            Statement::Update { table, .. } => match &table.relation {
+                TableFactor::Table { name, .. } => name,
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        let key = local_table_key(
+            &connection.tables,
+            &table_key(name, connection.current_database.as_deref()),
+        );
+
+        let synced = connection
+            .tables
+            .get(&key)
+            .is_some_and(|table| table.keys_synced);
+        if synced {
+            continue;
+        }
+
+        let schema = match schema_name(name) {
+            Some(schema) => upstream::string_literal(&schema),
+            None => "DATABASE()".to_string(),
+        };
+
+        // One row per unique index, e.g, `1:id` for the primary key or `0:tenant_id,email` for a unique key.
+        let sql = format!(
+            "SELECT CONCAT(INDEX_NAME = 'PRIMARY', ':', \
+             GROUP_CONCAT(COLUMN_NAME ORDER BY SEQ_IN_INDEX)) \
+             FROM information_schema.STATISTICS \
+             WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} AND NON_UNIQUE = 0 \
+             GROUP BY INDEX_NAME",
+            schema,
+            upstream::string_literal(&unqualified_name(name))
+        );
+
+        let indexes: Vec<String> = match upstream::query(connection, &sql) {
+            Ok(result) => result
+                .rows
+                .into_iter()
+                .filter_map(|row| row.into_values().next().flatten())
+                .collect(),
+            Err(e) => {
+                warn!("Unable to query upstream: {}", e);
+                vec![]
+            }
+        };
+
+        let mut table = connection.tables.entry(key).or_default();
+        for index in indexes {
+            let (primary, columns) = match index.split_once(':') {
+                Some(index) => index,
+                None => continue,
+            };
+            let columns: Vec<String> = columns.split(',').map(str::to_string).collect();
+
+            match primary {
+                "1" => table.primary_key = columns,
+                _ => table.unique_keys.push(columns),
+            }
+        }
+
+        debug!(
+            "Keys of {}: {:?} {:?}",
+            name, table.primary_key, table.unique_keys
+        );
+        table.keys_synced = true;
+    }
+}
+
+/// Resolves a WHERE clause made only of equalities (or IN lists) covering exactly the columns of the primary key or
+/// a unique key of the table into the keys of the rows it selects.
+/// Returns None if the clause could select rows by anything other than their key, or if the assignments modify a
+/// key column, in which case the UPDATE has to be recorded as a predicate.
+pub fn row_keys(
+    selection: &Expr,
+    assignments: &Assignments,
+    table: &LocalTable,
+) -> Option<Vec<RowKey>> {
+    let key_columns: Vec<&String> = table
+        .unique_keys
+        .iter()
+        .chain([&table.primary_key])
+        .flatten()
+        .collect();
+
+    if assignments.iter().any(|(column, _)| {
+        key_columns
+            .iter()
+            .any(|key_column| key_column.eq_ignore_ascii_case(column))
+    }) {
+        return None;
+    }
+
+    let mut conditions: HashMap<String, Vec<String>> = HashMap::new();
+    collect_conditions(selection, &mut conditions)?;
+
+    let key = [&table.primary_key]
+        .into_iter()
+        .chain(&table.unique_keys)
+        .find(|key| {
+            !key.is_empty()
+                && key.len() == conditions.len()
+                && key
+                    .iter()
+                    .all(|column| conditions.contains_key(&column.to_lowercase()))
+        })?;
+
+    let mut row_keys: Vec<RowKey> = vec![vec![]];
+    for column in key {
+        let values = &conditions[&column.to_lowercase()];
+        row_keys = row_keys
+            .into_iter()
+            .flat_map(|row_key| {
+                values.iter().map(move |value| {
+                    let mut row_key = row_key.clone();
+                    row_key.push((column.clone(), value.clone()));
+                    row_key
+                })
+            })
+            .collect();
+    }
+
+    Some(row_keys)
+}
+
+/// Collects the values each column is compared to, keyed by lowercase column name.
+fn collect_conditions(expr: &Expr, conditions: &mut HashMap<String, Vec<String>>) -> Option<()> {
+    let (column, values) = match expr {
+        Expr::Nested(expr) => return collect_conditions(expr, conditions),
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            collect_conditions(left, conditions)?;
+            return collect_conditions(right, conditions);
+        }
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Eq,
+            right,
+        } => match (column_name(left), column_name(right)) {
+            (Some(column), None) => (column, vec![key_value(right)?]),
+            (None, Some(column)) => (column, vec![key_value(left)?]),
+            _ => return None,
+        },
+        Expr::InList {
+            expr,
+            list,
+            negated: false,
+        } => (
+            column_name(expr)?,
+            list.iter().map(key_value).collect::<Option<Vec<_>>>()?,
+        ),
+        _ => return None,
+    };
+
+    // Repeated conditions on a column (e.g, `id = 1 AND id = 2`) are left to the evaluator.
+    match conditions.insert(column.to_lowercase(), values) {
+        Some(_) => None,
+        None => Some(()),
+    }
+}
+
+fn column_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(ident) => Some(ident.value.clone()),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|ident| ident.value.clone()),
+        _ => None,
+    }
+}
+
+/// Textual value of a literal, NULL never identifies a row.
+fn key_value(expr: &Expr) -> Option<String> {
+    if column_name(expr).is_some() {
+        return None;
+    }
+
+    literal_value(expr).ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::materialization::keys::row_keys;
+    use crate::materialization::{process_assignments, LocalTable};
+    use crate::mysql::command::{Command, MySqlCommand};
+    use sqlparser::ast::Statement;
+
+    fn resolve(query: &str) -> Option<Vec<Vec<(String, String)>>> {
+        let table = LocalTable {
+            primary_key: vec!["id".to_string()],
+            unique_keys: vec![vec!["tenant_id".to_string(), "email".to_string()]],
+            ..LocalTable::default()
+        };
+
+        match &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes())
+            .ast
+            .unwrap()[0]
+        {
+            Statement::Update {
+                selection: Some(selection),
+                assignments,
+                ..
+            } => row_keys(
+                selection,
+                &process_assignments(assignments).unwrap(),
+                &table,
+            ),
+            statement => panic!("Unexpected statement {:?}", statement),
+        }
+    }
+
+    fn key(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(column, value)| (column.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_row_keys() {
+        assert_eq!(
+            Some(vec![key(&[("id", "1")]), key(&[("id", "2")])]),
+            resolve("update account set status = 'A' where account.ID in (1, '2')")
+        );
+        assert_eq!(
+            Some(vec![key(&[("tenant_id", "7"), ("email", "a@b.c")])]),
+            resolve("update account set status = 'A' where email = 'a@b.c' and (7 = tenant_id)")
+        );
+
+        // Not selected by a complete key.
+        assert_eq!(
+            None,
+            resolve("update account set status = 'A' where email = 'a@b.c'")
+        );
+        assert_eq!(
+            None,
+            resolve("update account set status = 'A' where id = 1 and status = 'B'")
+        );
+        assert_eq!(
+            None,
+            resolve("update account set status = 'A' where id = 1 or id = 2")
+        );
+        assert_eq!(
+            None,
+            resolve("update account set status = 'A' where id = NULL")
+        );
+        // Key columns are modified.
+        assert_eq!(None, resolve("update account set id = 3 where id = 1"));
+    }
+}
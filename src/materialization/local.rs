@@ -0,0 +1,262 @@
+use crate::connection::Connection;
+use crate::materialization::collation::Collations;
+use crate::materialization::evaluator::{Parse, Parser};
+use crate::materialization::{
+    inserted_rows, literal_value, local_table_key, table_key, visible_divergences, DivergenceIndex,
+    LocalTable, Row,
+};
+use crate::mysql::text_result_set::TextResultSet;
+use sqlparser::ast::{
+    Delete, Expr, FromTable, GroupByExpr, LimitClause, ObjectName, Query, SelectItem, Statement,
+    TableFactor, TableObject,
+};
+
+/// Key of the table read or written by the statement, if it is a local-only table (see [`LocalTable::local_only`]).
+fn local_only_table(connection: &Connection, name: &ObjectName) -> Option<(String, LocalTable)> {
+    let table_name = table_key(name, connection.current_database.as_deref());
+    let table = connection
+        .tables
+        .get(&local_table_key(&connection.tables, &table_name))
+        .filter(|table| table.local_only)?
+        .clone();
+    Some((table_name, table))
+}
+
+/// Whether one of the statements writes to a local-only table, which has to be intercepted as it may not exist
+/// upstream.
+pub fn writes_local_table(connection: &Connection, ast: &Option<Vec<Statement>>) -> bool {
+    let statements = match ast {
+        Some(statements) => statements,
+        None => return false,
+    };
+
+    statements.iter().any(|statement| {
+        let name = match statement {
+            Statement::Insert(insert) => match &insert.table {
+                TableObject::TableName(name) => name,
+                _ => return false,
+            },
+            Statement::Update { table, .. } => match &table.relation {
+                TableFactor::Table { name, .. } => name,
+                _ => return false,
+            },
+            Statement::Delete(Delete {
+                from: FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from),
+                ..
+            }) => match from.first() {
+                Some(from) => match &from.relation {
+                    TableFactor::Table { name, .. } => name,
+                    _ => return false,
+                },
+                None => return false,
+            },
+            _ => return false,
+        };
+
+        local_only_table(connection, name).is_some()
+    })
+}
+
+/// Answers a SELECT reading a local-only table from the rows held by the proxy, instead of sending it upstream.
+/// Returns None if the query doesn't read a local-only table, and an error if it does in a way that isn't supported.
+pub fn select(
+    connection: &Connection,
+    ast: &Option<Vec<Statement>>,
+) -> Option<Result<TextResultSet, String>> {
+    let query = match ast.as_deref()? {
+        [Statement::Query(query)] => query,
+        _ => return None,
+    };
+    let select = query.body.as_select()?;
+    let name = match &select.from.first()?.relation {
+        TableFactor::Table { name, .. } => name,
+        _ => return None,
+    };
+    let (table_name, table) = local_only_table(connection, name)?;
+
+    if !is_plain_select(query) {
+        return Some(Err(format!(
+            "Only plain single table SELECTs are supported on local table {}",
+            table_name
+        )));
+    }
+
+    Some(select_rows(
+        connection,
+        &table_name,
+        &table,
+        query,
+        &select.projection,
+        &select.selection,
+    ))
+}
+
+/// Whether the query is a SELECT from a single table, without joins, grouping, DISTINCT or ordering, so that its
+/// result is made of the matching rows of the table taken one by one.
+pub fn is_plain_select(query: &Query) -> bool {
+    let select = match query.body.as_select() {
+        Some(select) => select,
+        None => return false,
+    };
+
+    select.from.len() == 1
+        && select.from[0].joins.is_empty()
+        && select.distinct.is_none()
+        && select.having.is_none()
+        && matches!(&select.group_by, GroupByExpr::Expressions(exprs, _) if exprs.is_empty())
+        && query.order_by.is_none()
+        && query.with.is_none()
+}
+
+fn select_rows(
+    connection: &Connection,
+    table_name: &str,
+    table: &LocalTable,
+    query: &Query,
+    projection: &[SelectItem],
+    selection: &Option<Expr>,
+) -> Result<TextResultSet, String> {
+    // (column of the table, name of the column in the result set)
+    let mut columns: Vec<(String, String)> = vec![];
+
+    for item in projection {
+        let (expr, alias) = match item {
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => {
+                columns.extend(table.columns.iter().map(|c| (c.clone(), c.clone())));
+                continue;
+            }
+            SelectItem::UnnamedExpr(expr) => (expr, None),
+            SelectItem::ExprWithAlias { expr, alias } => (expr, Some(alias.value.clone())),
+        };
+
+        let column = match expr {
+            Expr::Identifier(ident) => &ident.value,
+            Expr::CompoundIdentifier(idents) => &idents.last().unwrap().value,
+            expr => return Err(format!("Unsupported expression {} on a local table", expr)),
+        };
+        let column = table
+            .columns
+            .iter()
+            .find(|c| c.eq_ignore_ascii_case(column))
+            .ok_or(format!("Unknown column {} in {}", column, table_name))?;
+
+        columns.push((column.clone(), alias.unwrap_or(column.clone())));
+    }
+
+    let (offset, limit) = limits(query)?;
+
+    let collations = Collations::new();
+    let divergences = DivergenceIndex::new(
+        visible_divergences(connection, table_name),
+        collations.clone(),
+    );
+
+    // (row, sequence number of the insert)
+    let mut rows: Vec<(Row, u64)> = table.rows.iter().map(|row| (row.clone(), 0)).collect();
+    rows.extend(
+        inserted_rows(divergences.divergences())
+            .into_iter()
+            .map(|(row, _, sequence)| (row, sequence)),
+    );
+
+    let names: Vec<&str> = columns.iter().map(|(_, name)| name.as_str()).collect();
+    let mut result_set = TextResultSet::new(&names);
+
+    let selected = rows.into_iter().filter_map(|(mut row, since)| {
+        if !divergences.apply(&mut row, since) {
+            return None;
+        }
+
+        if let Some(selection) = selection {
+            if !Parse::evaluate(&row, &collations, selection).is_ok_and(|result| result.is_true()) {
+                return None;
+            }
+        }
+
+        Some(row)
+    });
+
+    for row in selected.skip(offset).take(limit.unwrap_or(usize::MAX)) {
+        result_set.push_row(
+            columns
+                .iter()
+                .map(|(column, _)| row.get(column).cloned().flatten())
+                .collect(),
+        );
+    }
+
+    Ok(result_set)
+}
+
+/// Offset and row count of the LIMIT clause.
+fn limits(query: &Query) -> Result<(usize, Option<usize>), String> {
+    let number = |expr: &Expr| {
+        literal_value(expr)
+            .ok()
+            .flatten()
+            .and_then(|value| value.parse::<usize>().ok())
+            .ok_or(format!("Unsupported limit {}", expr))
+    };
+
+    match &query.limit_clause {
+        None => Ok((0, None)),
+        Some(LimitClause::LimitOffset { limit, offset, .. }) => Ok((
+            offset
+                .as_ref()
+                .map(|offset| number(&offset.value))
+                .transpose()?
+                .unwrap_or(0),
+            limit.as_ref().map(number).transpose()?,
+        )),
+        Some(LimitClause::OffsetCommaLimit { offset, limit }) => {
+            Ok((number(offset)?, Some(number(limit)?)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Connection;
+    use crate::materialization::local::{select, writes_local_table};
+    use crate::materialization::seed::seed_from_sql;
+    use crate::mysql::command::{Command, MySqlCommand};
+
+    #[test]
+    fn test_select_local_table() {
+        let connection = Connection::default();
+        seed_from_sql(
+            &connection.tables,
+            "CREATE TABLE account (id int PRIMARY KEY, status varchar(16));
+            INSERT INTO account VALUES (1, 'ACTIVE'), (2, 'CLOSED'), (3, 'ACTIVE');",
+        );
+        let ast = |sql: &str| Command::from_bytes(MySqlCommand::ComQuery, sql.as_bytes()).ast;
+
+        let result_set = select(
+            &connection,
+            &ast("SELECT id AS account_id, status FROM account WHERE status = 'ACTIVE' LIMIT 1, 5"),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(vec!["account_id", "status"], result_set.columns);
+        assert_eq!(
+            vec![vec![Some("3".to_string()), Some("ACTIVE".to_string())]],
+            result_set.rows
+        );
+
+        assert!(
+            select(&connection, &ast("SELECT * FROM account ORDER BY id"))
+                .unwrap()
+                .is_err()
+        );
+        assert!(select(&connection, &ast("SELECT * FROM upstream_table")).is_none());
+
+        assert!(writes_local_table(
+            &connection,
+            &ast("UPDATE account SET status = 'CLOSED' WHERE id = 1")
+        ));
+        assert!(!writes_local_table(
+            &connection,
+            &ast("DELETE FROM upstream_table")
+        ));
+    }
+}
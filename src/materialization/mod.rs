@@ -1,43 +1,198 @@
+pub mod auto_increment;
+pub mod collation;
 pub mod evaluator;
+pub mod export;
+pub mod json;
+pub mod keys;
+pub mod local;
+pub mod namespace;
+pub mod persistence;
+pub mod seed;
+pub mod transaction;
+pub mod upstream;
 
+use crate::connection::Connection;
+use crate::materialization::collation::{Collation, Collations, DEFAULT_COLLATION_ID};
+use crate::materialization::evaluator::{numeric, Parse, Parser};
+use crate::mysql::accumulator::result_set::get_collations;
 use crate::util::cache::get_cache_ttl;
 use dashmap::DashMap;
 use log::{debug, error};
-use sqlparser::ast::{Assignment, AssignmentTarget, Expr, Statement, TableFactor};
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{
+    Assignment, AssignmentTarget, Delete, Expr, FromTable, Insert, ObjectName, SetExpr, Statement,
+    TableFactor, TableObject, UnaryOperator,
+};
 use std::collections::HashMap;
 use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-#[cfg(feature = "replay")]
-use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 use ttl_cache::TtlCache;
-use uuid::Uuid;
 
 /**
-Divergence from the state of the original table will be stored as follows:
-(Expr, HashMap<column_name, updated_value>), provided all conditions stipulated in the set are satisfied.
-TODO: Prevent conflict with multiple databases that have the same table names.
-TODO: Add accommodations for non-equality parameters.
+Divergence from the state of the original table will be stored as a list of [`Divergence`] entries, keyed by a
+monotonic sequence number denoting the order in which they were recorded.
 */
-pub type StateDifference = TtlCache<String, (Option<Expr>, HashMap<String, Option<String>>)>;
+pub type StateDifference = TtlCache<u64, RecordedDivergence>;
+
+/// Sequence number of the next recorded [`Divergence`], shared by all tables and connections.
+static DIVERGENCE_SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Divergence {
+    /// (Expr, Assignments), applied to rows which satisfy all conditions stipulated in the Expr.
+    Update(Option<Box<Expr>>, Assignments),
+    /// Row inserted through the proxy, along with the id generated for the auto-increment column (if any).
+    Insert(Row, Option<u64>),
+    /// Tombstone hiding rows which satisfy the Expr, or every row of the table if no Expr is present.
+    Delete(Option<Box<Expr>>),
+    /// Update of the single row identified by its primary or unique key, matched without evaluating a predicate.
+    Patch(RowKey, Assignments),
+}
+
+/// [`Divergence`] along with the time it expires at, which isn't exposed by the [`TtlCache`] holding it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedDivergence {
+    pub divergence: Divergence,
+    pub expires_at: SystemTime,
+}
+
+/// Row values keyed by column name.
+pub type Row = HashMap<String, Option<String>>;
+
+/// (column_name, value) pairs identifying a row by its primary key or a unique key, in key column order.
+pub type RowKey = Vec<(String, String)>;
+
+/// (column_name, Expr) pairs of an UPDATE statement, evaluated per row in the order they were written.
+pub type Assignments = Vec<(String, Expr)>;
 
 /**
 Divergence stored in the following format:
-DashMap<K, V>; where K : Table, V: StateDifference
+DashMap<K, V>; where K : Table key (see [`table_key`]), V: StateDifference
 */
 pub type StateDiffLog = Arc<DashMap<String, StateDifference>>;
 
-#[cfg(feature = "replay")]
-pub type ReplayLog = Option<Arc<Mutex<TtlCache<String, String>>>>;
+/**
+Tables known to the proxy, keyed by table key (see [`table_key`]), or by bare table name for tables seeded without
+a schema.
+Holds the column layout of each table along with rows that only exist in the proxy (e.g, seeded from SQL dump files).
+*/
+pub type TableStore = Arc<DashMap<String, LocalTable>>;
+
+#[derive(Debug, Default, Clone)]
+pub struct LocalTable {
+    pub columns: Vec<String>,
+    pub primary_key: Vec<String>,
+    pub unique_keys: Vec<Vec<String>>,
+    /// Whether the primary and unique keys have been learnt from the upstream server (or a seed file).
+    pub keys_synced: bool,
+    pub auto_increment: Option<String>,
+    /// Next value of the auto-increment column.
+    pub next_auto_increment: Option<u64>,
+    /// Whether the auto-increment counter has been reconciled with the upstream server.
+    pub auto_increment_synced: bool,
+    /// Whether the table was defined by a seed file, in which case it is read and written without going upstream.
+    pub local_only: bool,
+    pub defaults: Row,
+    pub rows: Vec<Row>,
+}
+
+/// Rows matched and changed by an intercepted UPDATE or DELETE.
+#[derive(Debug, Default, PartialEq)]
+pub struct AffectedRows {
+    pub matched: u64,
+    /// Matched rows whose values were modified, only present for UPDATE statements.
+    pub changed: Option<u64>,
+}
+
+/// Rows added by the INSERT statements of an intercepted write.
+#[derive(Debug, Default, PartialEq)]
+pub struct InsertedRows {
+    pub count: u64,
+    /// First id generated for an auto-increment column, which is reported as the last insert id.
+    pub last_insert_id: Option<u64>,
+}
+
+/// Records the divergence introduced by the statements in `ast`, returning the rows they inserted.
+pub fn get_diff(
+    map: &mut StateDiffLog,
+    tables: &TableStore,
+    current_database: Option<&str>,
+    ast: &Option<Vec<Statement>>,
+) -> InsertedRows {
+    let mut inserted = InsertedRows::default();
 
-pub fn get_diff(map: &mut StateDiffLog, ast: &Option<Vec<Statement>>) {
     if ast.is_none() {
-        return;
+        return inserted;
     }
 
     let statement = ast.as_ref().unwrap();
 
     for statement in statement {
-        if let Statement::Update {
+        if let Statement::Insert(insert) = statement {
+            let table_name = match &insert.table {
+                TableObject::TableName(name) => table_key(name, current_database),
+                _ => {
+                    panic_on_unsupported_behaviour("Insert query with non-relation table");
+                    continue;
+                }
+            };
+
+            let mut table = tables
+                .entry(local_table_key(tables, &table_name))
+                .or_default();
+            let rows = match insert_rows(insert, Some(&table)) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    panic_on_unsupported_behaviour(e);
+                    continue;
+                }
+            };
+
+            for mut row in rows {
+                let generated_id = auto_increment::assign_id(&mut table, &mut row);
+
+                inserted.count += 1;
+                inserted.last_insert_id = inserted.last_insert_id.or(generated_id);
+                debug!("{:?}", &row);
+
+                update_diff_log(map, &table_name, Divergence::Insert(row, generated_id));
+            }
+        } else if let Statement::Delete(Delete {
+            tables: delete_tables,
+            from,
+            using,
+            selection,
+            ..
+        }) = statement
+        {
+            let from = match from {
+                FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from) => from,
+            };
+
+            if !delete_tables.is_empty() || using.is_some() || from.len() != 1 {
+                panic_on_unsupported_behaviour("Multi-table delete statements are not supported!");
+                continue;
+            }
+
+            if let TableFactor::Table { name, .. } = &from[0].relation {
+                if !from[0].joins.is_empty() {
+                    panic_on_unsupported_behaviour(
+                        "Delete statements with joins are not supported!",
+                    );
+                    continue;
+                }
+
+                update_diff_log(
+                    map,
+                    &table_key(name, current_database),
+                    Divergence::Delete(selection.clone().map(Box::new)),
+                );
+            } else {
+                panic_on_unsupported_behaviour("Delete query with non-relation table");
+            }
+        } else if let Statement::Update {
             table,
             assignments,
             selection,
@@ -50,77 +205,434 @@ pub fn get_diff(map: &mut StateDiffLog, ast: &Option<Vec<Statement>>) {
                 ..
             } = &table.relation
             {
-                let table_name = name.0.last().unwrap().clone().to_string();
+                let table_name = table_key(name, current_database);
 
-                let processed_assignments = process_assignments(&assignments);
+                let assignments = match process_assignments(assignments) {
+                    Ok(assignments) => assignments,
+                    Err(e) => {
+                        panic_on_unsupported_behaviour(e);
+                        continue;
+                    }
+                };
+                debug!("{:?}", &assignments);
 
-                if processed_assignments.is_err() {
-                    panic_on_unsupported_behaviour(processed_assignments.err().unwrap());
-                    return;
-                }
-
-                debug!("{:?}", &processed_assignments);
+                let row_keys = selection.as_ref().and_then(|selection| {
+                    tables
+                        .get(&local_table_key(tables, &table_name))
+                        .and_then(|table| keys::row_keys(selection, &assignments, &table))
+                });
 
-                update_diff_log(map, selection, &table_name, processed_assignments);
+                match row_keys {
+                    // Updates of rows selected by key are recorded per row, see [`update_diff_log`].
+                    Some(row_keys) => {
+                        for row_key in row_keys {
+                            update_diff_log(
+                                map,
+                                &table_name,
+                                Divergence::Patch(row_key, assignments.clone()),
+                            );
+                        }
+                    }
+                    None => update_diff_log(
+                        map,
+                        &table_name,
+                        Divergence::Update(selection.clone().map(Box::new), assignments),
+                    ),
+                }
             } else {
                 panic_on_unsupported_behaviour("Update query with non-relation table");
             }
         }
     }
+
+    inserted
 }
 
-fn update_diff_log(
-    map: &mut StateDiffLog,
-    selection: &Option<Expr>,
-    table_name: &String,
-    processed_assignments: Result<HashMap<String, Option<String>>, &str>,
-) {
-    if !map.contains_key(table_name) {
-        map.insert(table_name.clone(), StateDifference::new(usize::MAX));
+/// Records the divergence introduced by the statements of an intercepted write, one statement at a time, so that the
+/// rows each UPDATE and DELETE affects are counted on top of the divergence of the statements before it.
+/// Returns the affected rows, summed up if there are several statements, and the inserted rows.
+pub fn record_write(
+    connection: &mut Connection,
+    ast: &Option<Vec<Statement>>,
+) -> (Option<AffectedRows>, InsertedRows) {
+    let mut total: Option<AffectedRows> = None;
+    let mut inserted = InsertedRows::default();
+
+    for statement in ast.iter().flatten() {
+        if let Some(affected_rows) = statement_affected_rows(connection, statement) {
+            let total = total.get_or_insert_with(AffectedRows::default);
+            total.matched += affected_rows.matched;
+            total.changed = match (total.changed, affected_rows.changed) {
+                (None, None) => None,
+                (total, changed) => Some(total.unwrap_or(0) + changed.unwrap_or(0)),
+            };
+        }
+
+        let statement = Some(vec![statement.clone()]);
+        auto_increment::prepare_counters(connection, &statement);
+        keys::prepare_keys(connection, &statement);
+
+        // Writes inside a transaction stay pending, visible only to this connection, until COMMIT.
+        let diff = match connection.transaction.buffering() {
+            true => connection.transaction.pending(&connection.diff),
+            false => &mut connection.diff,
+        };
+
+        let statement_inserted = get_diff(
+            diff,
+            &connection.tables,
+            connection.current_database.as_deref(),
+            &statement,
+        );
+        inserted.count += statement_inserted.count;
+        inserted.last_insert_id = inserted
+            .last_insert_id
+            .or(statement_inserted.last_insert_id);
+    }
+
+    (total, inserted)
+}
+
+fn statement_affected_rows(
+    connection: &mut Connection,
+    statement: &Statement,
+) -> Option<AffectedRows> {
+    let (name, selection, assignments) = match statement {
+        Statement::Update {
+            table,
+            assignments,
+            selection,
+            ..
+        } => match &table.relation {
+            TableFactor::Table { name, .. } if table.joins.is_empty() => (
+                name.clone(),
+                selection.clone(),
+                Some(process_assignments(assignments).ok()?),
+            ),
+            _ => return None,
+        },
+        Statement::Delete(Delete {
+            tables: delete_tables,
+            from: FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from),
+            selection,
+            ..
+        }) if delete_tables.is_empty() && from.len() == 1 && from[0].joins.is_empty() => {
+            match &from[0].relation {
+                TableFactor::Table { name, .. } => (name.clone(), selection.clone(), None),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    let table_name = table_key(&name, connection.current_database.as_deref());
+
+    let divergences = visible_divergences(connection, &table_name);
+
+    let local_table = connection
+        .tables
+        .get(&local_table_key(&connection.tables, &table_name))
+        .map(|table| table.clone());
+
+    let mut counted = AffectedRows {
+        matched: 0,
+        changed: assignments.as_ref().map(|_| 0),
+    };
+    let mut rows: Vec<(Row, u64)> = vec![];
+    let mut collations = Collations::new();
+
+    if !local_table.as_ref().is_some_and(|table| table.local_only) {
+        match upstream_affected_rows(connection, &name, &selection, &assignments, &divergences) {
+            Ok(UpstreamRows::Counted(affected_rows)) => counted = affected_rows,
+            Ok(UpstreamRows::Fetched(result)) => {
+                collations = get_collations(&result.columns);
+                rows.extend(result.rows.into_iter().map(|row| (row, 0)));
+            }
+            Err(e) => debug!("Unable to fetch rows from upstream, {}", e),
+        }
     }
 
-    let mut state_difference = map.get_mut(table_name).unwrap();
+    // Rows which existed before any divergence was recorded.
+    if let Some(table) = local_table {
+        rows.extend(table.rows.into_iter().map(|row| (row, 0)));
+    }
 
-    // YUCKKKKKKK. THIS IS TRASH.
+    for (row, _, sequence) in inserted_rows(&divergences) {
+        rows.push((row, sequence));
+    }
 
-    state_difference.insert(
-        Uuid::new_v4().to_string(),
-        (selection.clone(), processed_assignments.unwrap()),
-        get_cache_ttl(),
-    );
+    let divergences = DivergenceIndex::new(divergences, collations.clone());
+    let affected_rows =
+        count_affected_rows(rows, &collations, &divergences, &selection, &assignments);
+
+    Some(AffectedRows {
+        matched: counted.matched + affected_rows.matched,
+        changed: counted
+            .changed
+            .zip(affected_rows.changed)
+            .map(|(counted, changed)| counted + changed),
+    })
 }
 
-fn process_assignments(
-    assignments: &Vec<Assignment>,
-) -> Result<HashMap<String, Option<String>>, &'static str> {
-    let mut processed_assignments = HashMap::default();
+/// Upstream rows affected by a statement, either counted by the server or to be checked by the proxy.
+enum UpstreamRows {
+    Counted(AffectedRows),
+    Fetched(upstream::QueryResult),
+}
 
-    for assignment in assignments {
-        if let Expr::Value(value) = assignment.value.clone() {
-            let assignment_target = match assignment.target.clone() {
-                AssignmentTarget::Tuple(_) => {
-                    panic_on_unsupported_behaviour("Tuple assignment targets are not supported!");
-                    return Err("Tuple assignment targets are not supported!");
+fn upstream_affected_rows(
+    connection: &mut Connection,
+    name: &ObjectName,
+    selection: &Option<Expr>,
+    assignments: &Option<Assignments>,
+    divergences: &[(u64, Divergence)],
+) -> Result<UpstreamRows, String> {
+    let (sql, counted) = pushdown_query(name, selection, assignments, divergences);
+    let result = upstream::query(connection, &sql)?;
+
+    if !counted {
+        return Ok(UpstreamRows::Fetched(result));
+    }
+
+    let count = |column: &str| {
+        result
+            .rows
+            .first()
+            .and_then(|row| row.get(column).cloned().flatten())
+            .and_then(|count| count.parse::<u64>().ok())
+            .unwrap_or(0)
+    };
+
+    Ok(UpstreamRows::Counted(AffectedRows {
+        matched: count("matched"),
+        changed: assignments.as_ref().map(|_| count("changed")),
+    }))
+}
+
+/// Query pushing the WHERE clause of an UPDATE or DELETE down to the server, and whether it counts the affected rows.
+/// The server counts them unless recorded updates or deletes of the table may differ from its own state, in which
+/// case the rows are fetched to be checked one by one. These include the rows recorded updates could have made match
+/// the WHERE clause, i.e, the ones matching their own WHERE clause, or key for patches.
+fn pushdown_query(
+    name: &ObjectName,
+    selection: &Option<Expr>,
+    assignments: &Option<Assignments>,
+    divergences: &[(u64, Divergence)],
+) -> (String, bool) {
+    let overlapping = divergences
+        .iter()
+        .any(|(_, divergence)| !matches!(divergence, Divergence::Insert(..)));
+
+    // A single assignment can be checked by the server, as it only sees the values of the row before the update.
+    let countable = match assignments {
+        Some(assignments) => assignments.len() == 1,
+        None => true,
+    };
+
+    if !overlapping && countable {
+        let changed = match assignments
+            .as_ref()
+            .and_then(|assignments| assignments.first())
+        {
+            Some((column, value)) => format!(
+                ", COUNT(CASE WHEN NOT ({} <=> ({})) THEN 1 END) AS changed",
+                upstream::quoted_identifier(column),
+                value
+            ),
+            None => String::new(),
+        };
+
+        let sql = match selection {
+            Some(selection) => format!(
+                "SELECT COUNT(*) AS matched{} FROM {} WHERE {}",
+                changed, name, selection
+            ),
+            None => format!("SELECT COUNT(*) AS matched{} FROM {}", changed, name),
+        };
+        return (sql, true);
+    }
+
+    let mut conditions: Vec<String> = vec![];
+
+    if let Some(selection) = selection {
+        conditions.push(selection.to_string());
+
+        for (_, divergence) in divergences {
+            match divergence {
+                Divergence::Update(Some(selection), _) => conditions.push(selection.to_string()),
+                Divergence::Update(None, _) => {
+                    conditions.clear();
+                    break;
                 }
-                AssignmentTarget::ColumnName(name) => Some(name),
+                Divergence::Patch(row_key, _) => conditions.push(
+                    row_key
+                        .iter()
+                        .map(|(column, value)| {
+                            format!(
+                                "{} = {}",
+                                upstream::quoted_identifier(column),
+                                upstream::string_literal(value)
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" AND "),
+                ),
+                Divergence::Insert(..) | Divergence::Delete(..) => {}
+            }
+        }
+    }
+
+    // Every column is fetched, as recorded divergences may refer to any of them.
+    let sql = match conditions.is_empty() {
+        true => format!("SELECT * FROM {}", name),
+        false => format!(
+            "SELECT * FROM {} WHERE ({})",
+            name,
+            conditions.join(") OR (")
+        ),
+    };
+    (sql, false)
+}
+
+fn count_affected_rows(
+    rows: Vec<(Row, u64)>,
+    collations: &Collations,
+    divergences: &DivergenceIndex,
+    selection: &Option<Expr>,
+    assignments: &Option<Assignments>,
+) -> AffectedRows {
+    let mut affected_rows = AffectedRows {
+        matched: 0,
+        changed: assignments.as_ref().map(|_| 0),
+    };
+
+    for (mut row, since) in rows {
+        if !divergences.apply(&mut row, since) {
+            continue;
+        }
+
+        if let Some(selection) = selection {
+            if !Parse::evaluate(&row, collations, selection).is_ok_and(|result| result.is_true()) {
+                continue;
+            }
+        }
+
+        affected_rows.matched += 1;
+
+        if let (Some(assignments), Some(changed)) = (assignments, &mut affected_rows.changed) {
+            let mut updated_row = row.clone();
+            apply_assignments(&mut updated_row, collations, assignments);
+
+            if updated_row != row {
+                *changed += 1;
+            }
+        }
+    }
+
+    affected_rows
+}
+
+/// Records the divergence under the next sequence number.
+/// A patch is merged into the previous patch of the same row, provided only patches of other rows on the same key
+/// were recorded since, so that the table holds at most one patch per row in a run of key-resolved updates.
+fn update_diff_log(map: &mut StateDiffLog, table_name: &str, divergence: Divergence) {
+    let divergence = match divergence {
+        Divergence::Patch(row_key, assignments) => {
+            let mut state_difference = map
+                .entry(table_name.to_string())
+                .or_insert_with(|| StateDifference::new(usize::MAX));
+            let same_columns = |other: &RowKey| {
+                other.len() == row_key.len()
+                    && other.iter().zip(&row_key).all(|((l, _), (r, _))| l == r)
             };
 
-            let column_name = assignment_target
-                .unwrap()
-                .0
-                .last()
-                .unwrap()
-                .as_ident()
-                .unwrap()
-                .value
-                .clone();
+            let mut previous = None;
+            for (sequence, divergence) in
+                ordered_divergences(&mut state_difference).into_iter().rev()
+            {
+                match divergence {
+                    Divergence::Patch(other, other_assignments) if same_columns(&other) => {
+                        if other == row_key {
+                            previous = Some((sequence, other_assignments));
+                            break;
+                        }
+                    }
+                    _ => break,
+                }
+            }
 
-            let updated_value = value.clone().into_string();
-            processed_assignments.insert(column_name, updated_value);
-        } else {
-            panic_on_unsupported_behaviour("Non-value based assignment in update statement!");
-            return Err("Non-value based assignment in update statement!");
+            match previous {
+                Some((sequence, mut merged)) => {
+                    state_difference.remove(&sequence);
+                    merged.extend(assignments);
+                    Divergence::Patch(row_key, collapse_assignments(merged))
+                }
+                None => Divergence::Patch(row_key, assignments),
+            }
         }
+        divergence => divergence,
+    };
+
+    insert_divergence(
+        map,
+        table_name,
+        DIVERGENCE_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        divergence,
+        get_cache_ttl(),
+    );
+}
+
+fn insert_divergence(
+    map: &StateDiffLog,
+    table_name: &str,
+    sequence: u64,
+    divergence: Divergence,
+    ttl: Duration,
+) {
+    let mut state_difference = map
+        .entry(table_name.to_string())
+        .or_insert_with(|| StateDifference::new(usize::MAX));
+
+    state_difference.insert(
+        sequence,
+        RecordedDivergence {
+            divergence,
+            expires_at: SystemTime::now() + ttl,
+        },
+        ttl,
+    );
+}
+
+/// Keeps only the last assignment to each column, if every assignment is a literal and none depends on another.
+fn collapse_assignments(assignments: Assignments) -> Assignments {
+    if !assignments
+        .iter()
+        .all(|(_, expr)| matches!(expr, Expr::Value(_)))
+    {
+        return assignments;
+    }
+
+    let mut collapsed: Assignments = vec![];
+    for (column, expr) in assignments {
+        collapsed.retain(|(other, _)| !other.eq_ignore_ascii_case(&column));
+        collapsed.push((column, expr));
+    }
+    collapsed
+}
+
+fn process_assignments(assignments: &Vec<Assignment>) -> Result<Assignments, &'static str> {
+    let mut processed_assignments = Assignments::default();
+
+    for assignment in assignments {
+        let column_name = match &assignment.target {
+            AssignmentTarget::Tuple(_) => {
+                panic_on_unsupported_behaviour("Tuple assignment targets are not supported!");
+                return Err("Tuple assignment targets are not supported!");
+            }
+            AssignmentTarget::ColumnName(name) => unqualified_name(name),
+        };
+
+        processed_assignments.push((column_name, assignment.value.clone()));
     }
 
     Ok(processed_assignments)
@@ -133,3 +645,741 @@ fn panic_on_unsupported_behaviour(error: &str) {
         error!("Ignoring unsupported query: {}", error);
     }
 }
+
+/// Values of the key columns of the row, telling apart the rows of a table.
+pub fn key_values(row: &Row, columns: &[String]) -> Vec<Option<String>> {
+    columns
+        .iter()
+        .map(|column| row.get(column).cloned().flatten())
+        .collect()
+}
+
+/// Divergences of a table along with their sequence numbers, in the order they were recorded.
+pub fn ordered_divergences(diff: &mut StateDifference) -> Vec<(u64, Divergence)> {
+    let mut divergences: Vec<(u64, Divergence)> = diff
+        .iter()
+        .map(|(sequence, recorded)| (*sequence, recorded.divergence.clone()))
+        .collect();
+
+    divergences.sort_by_key(|(sequence, _)| *sequence);
+    divergences
+}
+
+/// Number of divergences recorded in the diff log.
+pub fn divergence_count(diff: &StateDiffLog) -> usize {
+    diff.iter_mut()
+        .map(|mut entry| entry.value_mut().iter().count())
+        .sum()
+}
+
+/// Removes the divergences of one or every table, returning the number of divergences removed.
+pub fn clear_divergences(diff: &StateDiffLog, table_key: Option<&str>) -> usize {
+    let mut count = 0;
+
+    diff.retain(|key, state_difference| {
+        if table_key.is_some_and(|table_key| table_key != key) {
+            return true;
+        }
+        count += state_difference.iter().count();
+        false
+    });
+
+    count
+}
+
+/// Divergences of a table visible to the connection, i.e, those committed to the shared log along with the ones
+/// pending in its own transaction, in the order they were recorded.
+pub fn visible_divergences(connection: &Connection, key: &str) -> Vec<(u64, Divergence)> {
+    let mut divergences = vec![];
+
+    let pending = connection.transaction.pending_in(&connection.diff);
+    for log in [Some(&connection.diff), pending].into_iter().flatten() {
+        if let Some(mut diff) = log.get_mut(key) {
+            divergences.extend(ordered_divergences(&mut diff));
+        }
+    }
+
+    divergences.sort_by_key(|(sequence, _)| *sequence);
+    divergences
+}
+
+/// Rows inserted through the proxy, along with the generated id and the sequence number of the insert.
+pub fn inserted_rows(divergences: &[(u64, Divergence)]) -> Vec<(Row, Option<u64>, u64)> {
+    divergences
+        .iter()
+        .filter_map(|(sequence, divergence)| match divergence {
+            Divergence::Insert(row, generated_id) => Some((row.clone(), *generated_id, *sequence)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Divergences of a table, with its patches indexed by the key of their row so that each row is only checked against
+/// its own patches. Built once per result set, as keys are compared according to the collations of its columns.
+#[derive(Debug, Default, Clone)]
+pub struct DivergenceIndex {
+    divergences: Vec<(u64, Divergence)>,
+    collations: Collations,
+    /// Columns of the keys patches were recorded on, e.g, the primary key.
+    key_columns: Vec<Vec<String>>,
+    /// Positions of the patches in `divergences`, keyed by the normalized key of their row.
+    patches: HashMap<RowKey, Vec<usize>>,
+}
+
+impl DivergenceIndex {
+    pub fn new(divergences: Vec<(u64, Divergence)>, collations: Collations) -> DivergenceIndex {
+        let mut index = DivergenceIndex {
+            collations,
+            ..DivergenceIndex::default()
+        };
+
+        for (position, (_, divergence)) in divergences.iter().enumerate() {
+            if let Divergence::Patch(row_key, _) = divergence {
+                let columns: Vec<String> =
+                    row_key.iter().map(|(column, _)| column.clone()).collect();
+                let key: RowKey = row_key
+                    .iter()
+                    .map(|(column, value)| (column.clone(), index.normalized(column, value)))
+                    .collect();
+
+                if !index.key_columns.contains(&columns) {
+                    index.key_columns.push(columns);
+                }
+                index.patches.entry(key).or_default().push(position);
+            }
+        }
+
+        index.divergences = divergences;
+        index
+    }
+
+    pub fn divergences(&self) -> &[(u64, Divergence)] {
+        &self.divergences
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.divergences.is_empty()
+    }
+
+    /// Layers the updates and deletes recorded after sequence number `since` onto the row, each one applied to the
+    /// result of the previous. Returns false if the row has been deleted.
+    pub fn apply(&self, row: &mut Row, since: u64) -> bool {
+        let mut patches = self.patches_of(row);
+
+        for (position, (sequence, divergence)) in self.divergences.iter().enumerate() {
+            if *sequence <= since {
+                continue;
+            }
+
+            let (selection, assignments) = match divergence {
+                Divergence::Update(selection, assignments) => (selection, Some(assignments)),
+                Divergence::Delete(selection) => (selection, None),
+                Divergence::Patch(_, assignments) => {
+                    if patches.contains(&position) {
+                        apply_assignments(row, &self.collations, assignments);
+                    }
+                    continue;
+                }
+                Divergence::Insert(..) => continue,
+            };
+
+            let matches = match selection {
+                None => true,
+                Some(selection) => Parse::evaluate(row, &self.collations, selection)
+                    .is_ok_and(|result| result.is_true()),
+            };
+
+            match (matches, assignments) {
+                (true, Some(assignments)) => {
+                    apply_assignments(row, &self.collations, assignments);
+                    // Patches never modify key columns, but updates recorded as a predicate may.
+                    patches = self.patches_of(row);
+                }
+                (true, None) => return false,
+                (false, _) => {}
+            }
+        }
+
+        true
+    }
+
+    /// Positions of the patches recorded on the keys of the row.
+    fn patches_of(&self, row: &Row) -> Vec<usize> {
+        self.key_columns
+            .iter()
+            .filter_map(|columns| {
+                let key = columns
+                    .iter()
+                    .map(|column| {
+                        let value = row.get(column)?.as_deref()?;
+                        Some((column.clone(), self.normalized(column, value)))
+                    })
+                    .collect::<Option<RowKey>>()?;
+                self.patches.get(&key)
+            })
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Normalized form of a key value, equal for values the evaluator compares as equal, i.e, numbers by value (`1.0`
+    /// and `'01'` match `1`) and strings according to the collation of the column.
+    fn normalized(&self, column: &str, value: &str) -> String {
+        match numeric(value) {
+            // Adding 0 turns -0 into 0.
+            Some(number) => (number + 0.0).to_string(),
+            None => Collation::from_id(
+                self.collations
+                    .get(column)
+                    .copied()
+                    .unwrap_or(DEFAULT_COLLATION_ID),
+            )
+            .key(value),
+        }
+    }
+}
+
+/// Evaluates the assignments against the row, each one seeing the values set by the ones before it.
+pub fn apply_assignments(row: &mut Row, collations: &Collations, assignments: &Assignments) {
+    for (column_name, expr) in assignments {
+        match Parse::evaluate(row, collations, expr) {
+            Ok(result) => {
+                row.insert(column_name.clone(), result.into_value());
+            }
+            Err(e) => debug!("Unable to evaluate assignment to {}: {}", column_name, e),
+        }
+    }
+}
+
+pub fn unqualified_name(name: &ObjectName) -> String {
+    name.0
+        .last()
+        .and_then(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+        .unwrap_or_default()
+}
+
+/// Schema qualifying `name`, e.g, `shop` in `shop.account`.
+pub fn schema_name(name: &ObjectName) -> Option<String> {
+    name.0
+        .iter()
+        .rev()
+        .nth(1)
+        .and_then(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+}
+
+/// Key of a table in the [`StateDiffLog`] and [`TableStore`], i.e, `schema.table`.
+/// Unqualified names resolve to the current database of the connection, and stay unqualified if none is selected.
+pub fn table_key(name: &ObjectName, current_database: Option<&str>) -> String {
+    match schema_name(name).as_deref().or(current_database) {
+        Some(schema) => format!("{}.{}", schema, unqualified_name(name)),
+        None => unqualified_name(name),
+    }
+}
+
+/// Resolves `key` against the [`TableStore`], falling back to the bare table name for tables seeded without a schema.
+pub fn local_table_key(tables: &TableStore, key: &str) -> String {
+    match key.split_once('.') {
+        Some((_, table)) if !tables.contains_key(key) && tables.contains_key(table) => {
+            table.to_string()
+        }
+        _ => key.to_string(),
+    }
+}
+
+/// Resolves the rows of an INSERT statement into column/value maps.
+/// Columns omitted from the statement are populated from the defaults of the table, if known.
+pub fn insert_rows(insert: &Insert, table: Option<&LocalTable>) -> Result<Vec<Row>, &'static str> {
+    let columns: Vec<String> = match insert.columns.is_empty() {
+        true => match table {
+            Some(table) if !table.columns.is_empty() => table.columns.clone(),
+            _ => return Err("Insert without column list into a table with unknown layout!"),
+        },
+        false => insert.columns.iter().map(|c| c.value.clone()).collect(),
+    };
+
+    let mut tuples: Vec<Vec<(String, &Expr)>> = vec![];
+
+    if !insert.assignments.is_empty() {
+        let mut tuple = vec![];
+        for assignment in &insert.assignments {
+            let column_name = match &assignment.target {
+                AssignmentTarget::ColumnName(name) => unqualified_name(name),
+                AssignmentTarget::Tuple(_) => {
+                    return Err("Tuple assignment targets are not supported!")
+                }
+            };
+            tuple.push((column_name, &assignment.value));
+        }
+        tuples.push(tuple);
+    } else if let Some(source) = &insert.source {
+        match source.body.as_ref() {
+            SetExpr::Values(values) => {
+                for values_row in &values.rows {
+                    if values_row.len() != columns.len() {
+                        return Err("Column count doesn't match value count in insert statement!");
+                    }
+                    tuples.push(columns.iter().cloned().zip(values_row.iter()).collect());
+                }
+            }
+            _ => return Err("Only VALUES based inserts are supported!"),
+        }
+    }
+
+    let mut rows = vec![];
+    for tuple in tuples {
+        let mut row = table.map(|t| t.defaults.clone()).unwrap_or_default();
+        for (column_name, expr) in tuple {
+            row.insert(column_name, literal_value(expr)?);
+        }
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Resolves a literal expression (e.g, a value in an INSERT tuple) to the textual form MySQL would return.
+pub fn literal_value(expr: &Expr) -> Result<Option<String>, &'static str> {
+    if let Expr::UnaryOp {
+        op: UnaryOperator::Minus,
+        expr,
+    } = expr
+    {
+        return Ok(literal_value(expr)?.map(|value| format!("-{}", value)));
+    }
+
+    Ok(Parse::evaluate(&HashMap::new(), &Collations::new(), expr)?.into_value())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Connection;
+    use crate::materialization::collation::Collations;
+    use crate::materialization::seed::seed_from_sql;
+    use crate::materialization::{
+        count_affected_rows, get_diff, inserted_rows, local_table_key, ordered_divergences,
+        process_assignments, pushdown_query, record_write, AffectedRows, Divergence,
+        DivergenceIndex, InsertedRows, LocalTable, Row, StateDiffLog, TableStore,
+    };
+    use crate::mysql::command::{Command, MySqlCommand};
+    use sqlparser::ast::{Statement, TableFactor};
+
+    fn row(id: &str, status: &str) -> Row {
+        Row::from([
+            ("id".to_string(), Some(id.to_string())),
+            ("status".to_string(), Some(status.to_string())),
+        ])
+    }
+
+    #[test]
+    fn test_count_affected_rows() {
+        let mut diff_log = StateDiffLog::default();
+
+        get_diff(
+            &mut diff_log,
+            &TableStore::default(),
+            None,
+            &Command::from_bytes(
+                MySqlCommand::ComQuery,
+                b"update account set status = 'CLOSED' where id = 2",
+            )
+            .ast,
+        );
+
+        let ast = Command::from_bytes(
+            MySqlCommand::ComQuery,
+            b"update account set status = 'CLOSED' where status = 'ACTIVE' or id = 2",
+        )
+        .ast
+        .unwrap();
+
+        if let Statement::Update {
+            selection,
+            assignments,
+            ..
+        } = &ast[0]
+        {
+            let affected_rows = count_affected_rows(
+                vec![
+                    (row("1", "ACTIVE"), 0),
+                    (row("2", "ACTIVE"), 0),
+                    (row("3", "NEW"), 0),
+                ],
+                &Collations::new(),
+                &DivergenceIndex::new(
+                    ordered_divergences(&mut diff_log.get_mut("account").unwrap()),
+                    Collations::new(),
+                ),
+                selection,
+                &process_assignments(assignments).ok(),
+            );
+
+            assert_eq!(
+                AffectedRows {
+                    matched: 2,
+                    changed: Some(1),
+                },
+                affected_rows
+            );
+        }
+    }
+
+    #[test]
+    fn test_inserted_rows_counted() {
+        let tables = TableStore::default();
+        tables.insert(
+            "account".to_string(),
+            LocalTable {
+                auto_increment: Some("id".to_string()),
+                next_auto_increment: Some(7),
+                ..LocalTable::default()
+            },
+        );
+
+        let inserted = get_diff(
+            &mut StateDiffLog::default(),
+            &tables,
+            None,
+            &Command::from_bytes(
+                MySqlCommand::ComQuery,
+                b"insert into account (status) values ('A'), ('B'), ('C')",
+            )
+            .ast,
+        );
+
+        assert_eq!(
+            InsertedRows {
+                count: 3,
+                last_insert_id: Some(7),
+            },
+            inserted
+        );
+    }
+
+    #[test]
+    fn test_unsupported_update_skipped() {
+        let mut diff_log = StateDiffLog::default();
+
+        let inserted = get_diff(
+            &mut diff_log,
+            &TableStore::default(),
+            None,
+            &Command::from_bytes(
+                MySqlCommand::ComQuery,
+                b"update account set (status, id) = ('CLOSED', 1); insert into account (id) values (5)",
+            )
+            .ast,
+        );
+
+        assert_eq!(
+            InsertedRows {
+                count: 1,
+                last_insert_id: None,
+            },
+            inserted
+        );
+        let divergences = ordered_divergences(&mut diff_log.get_mut("account").unwrap());
+        assert!(matches!(divergences[..], [(_, Divergence::Insert(..))]));
+    }
+
+    #[test]
+    fn test_pushdown_query() {
+        let mut diff_log = StateDiffLog::default();
+        let ast = |sql: &str| {
+            Command::from_bytes(MySqlCommand::ComQuery, sql.as_bytes())
+                .ast
+                .unwrap()
+        };
+        let query = |diff_log: &mut StateDiffLog, sql: &str| {
+            let statement = ast(sql).remove(0);
+            let (name, selection, assignments) = match statement {
+                Statement::Update {
+                    table,
+                    selection,
+                    assignments,
+                    ..
+                } => match table.relation {
+                    TableFactor::Table { name, .. } => (
+                        name,
+                        selection,
+                        Some(process_assignments(&assignments).unwrap()),
+                    ),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            };
+            let divergences = diff_log
+                .get_mut("account")
+                .map(|mut diff| ordered_divergences(&mut diff))
+                .unwrap_or_default();
+            pushdown_query(&name, &selection, &assignments, &divergences)
+        };
+
+        assert_eq!(
+            (
+                "SELECT COUNT(*) AS matched, COUNT(CASE WHEN NOT (`status` <=> ('CLOSED')) THEN 1 END) AS changed \
+                FROM account WHERE id > 2"
+                    .to_string(),
+                true
+            ),
+            query(
+                &mut diff_log,
+                "update account set status = 'CLOSED' where id > 2"
+            )
+        );
+
+        get_diff(
+            &mut diff_log,
+            &TableStore::default(),
+            None,
+            &Some(ast("update account set id = 3 where status = 'NEW'")),
+        );
+
+        assert_eq!(
+            (
+                "SELECT * FROM account WHERE (id > 2) OR (status = 'NEW')".to_string(),
+                false
+            ),
+            query(
+                &mut diff_log,
+                "update account set status = 'CLOSED' where id > 2"
+            )
+        );
+    }
+
+    #[test]
+    fn test_statements_counted_in_turn() {
+        let mut connection = Connection::default();
+        seed_from_sql(
+            &connection.tables,
+            "CREATE TABLE t (id int PRIMARY KEY, a int, b int);
+            INSERT INTO t VALUES (5, 0, 0), (6, 1, 0);",
+        );
+
+        let (affected_rows, _) = record_write(
+            &mut connection,
+            &Command::from_bytes(
+                MySqlCommand::ComQuery,
+                b"UPDATE t SET a = 1 WHERE id = 5; UPDATE t SET b = 2 WHERE a = 1",
+            )
+            .ast,
+        );
+
+        // The second UPDATE matches the row the first one changed.
+        assert_eq!(
+            Some(AffectedRows {
+                matched: 3,
+                changed: Some(3),
+            }),
+            affected_rows
+        );
+    }
+
+    #[test]
+    fn test_expression_updates_applied_in_order() {
+        let mut diff_log = StateDiffLog::default();
+
+        for query in [
+            "update account set balance = balance + 10, status = concat(status, '-', balance) where id = 1",
+            "update account set balance = balance * 2 where balance > 15",
+        ] {
+            get_diff(
+                &mut diff_log,
+                &TableStore::default(),
+                None,
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        let mut row = row("1", "ACTIVE");
+        row.insert("balance".to_string(), Some("7".to_string()));
+
+        let mut diff = diff_log.get_mut("account").unwrap();
+        assert!(
+            DivergenceIndex::new(ordered_divergences(&mut diff), Collations::new())
+                .apply(&mut row, 0)
+        );
+
+        assert_eq!(Some(&Some("34".to_string())), row.get("balance"));
+        assert_eq!(Some(&Some("ACTIVE-17".to_string())), row.get("status"));
+    }
+
+    #[test]
+    fn test_divergences_layered_in_sequence() {
+        let mut diff_log = StateDiffLog::default();
+
+        for query in [
+            "update account set status = 'CLOSED'",
+            "delete from account where id = 1",
+            "insert into account (id, status) values (1, 'NEW')",
+            "update account set status = concat(status, '!') where id = 1",
+        ] {
+            get_diff(
+                &mut diff_log,
+                &TableStore::default(),
+                None,
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        let mut diff = diff_log.get_mut("account").unwrap();
+        let divergences = ordered_divergences(&mut diff);
+
+        let mut upstream_row = row("1", "ACTIVE");
+        assert!(
+            !DivergenceIndex::new(divergences.clone(), Collations::new())
+                .apply(&mut upstream_row, 0)
+        );
+
+        let mut other_row = row("2", "ACTIVE");
+        assert!(
+            DivergenceIndex::new(divergences.clone(), Collations::new()).apply(&mut other_row, 0)
+        );
+        assert_eq!(Some(&Some("CLOSED".to_string())), other_row.get("status"));
+
+        let (mut inserted_row, _, since) = inserted_rows(&divergences).remove(0);
+        assert!(DivergenceIndex::new(divergences.clone(), Collations::new())
+            .apply(&mut inserted_row, since));
+        assert_eq!(Some(&Some("NEW!".to_string())), inserted_row.get("status"));
+    }
+
+    #[test]
+    fn test_diffs_scoped_by_schema() {
+        let mut diff_log = StateDiffLog::default();
+        let tables = TableStore::default();
+        tables.insert("account".to_string(), LocalTable::default());
+
+        for query in [
+            "update account set status = 'CLOSED'",
+            "delete from crm.account where id = 1",
+        ] {
+            get_diff(
+                &mut diff_log,
+                &tables,
+                Some("shop"),
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        assert!(diff_log.contains_key("shop.account"));
+        assert!(diff_log.contains_key("crm.account"));
+        assert!(!diff_log.contains_key("account"));
+
+        assert_eq!("account", local_table_key(&tables, "shop.account"));
+        assert_eq!("crm.orders", local_table_key(&tables, "crm.orders"));
+    }
+
+    #[test]
+    fn test_key_resolved_updates_collapse_into_patches() {
+        let mut diff_log = StateDiffLog::default();
+        let tables = TableStore::default();
+        tables.insert(
+            "shop.account".to_string(),
+            LocalTable {
+                primary_key: vec!["id".to_string()],
+                ..LocalTable::default()
+            },
+        );
+
+        for query in [
+            "update account set status = 'A' where id = 1",
+            "update account set status = 'B' where id in (2, 3)",
+            "update account set status = 'C', balance = 5 where id = 1",
+            "update account set status = 'D' where status = 'B'",
+            "update account set balance = balance + 1 where id = 1",
+        ] {
+            get_diff(
+                &mut diff_log,
+                &tables,
+                Some("shop"),
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        let divergences = ordered_divergences(&mut diff_log.get_mut("shop.account").unwrap());
+        let patches: Vec<String> = divergences
+            .iter()
+            .map(|(_, divergence)| match divergence {
+                Divergence::Patch(row_key, assignments) => format!(
+                    "{:?} {}",
+                    row_key,
+                    assignments
+                        .iter()
+                        .map(|(column, expr)| format!("{} = {}", column, expr))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Divergence::Update(..) => "update".to_string(),
+                divergence => panic!("Unexpected divergence {:?}", divergence),
+            })
+            .collect();
+
+        // The first two patches of row 1 merge, the predicate update keeps the last one apart.
+        assert_eq!(
+            vec![
+                r#"[("id", "2")] status = 'B'"#,
+                r#"[("id", "3")] status = 'B'"#,
+                r#"[("id", "1")] status = 'C', balance = 5"#,
+                "update",
+                r#"[("id", "1")] balance = balance + 1"#,
+            ],
+            patches
+        );
+
+        let mut row = row("1", "NEW");
+        assert!(DivergenceIndex::new(divergences.clone(), Collations::new()).apply(&mut row, 0));
+        assert_eq!(Some(&Some("C".to_string())), row.get("status"));
+        assert_eq!(Some(&Some("6".to_string())), row.get("balance"));
+
+        let mut row = super::tests::row("3", "NEW");
+        assert!(DivergenceIndex::new(divergences.clone(), Collations::new()).apply(&mut row, 0));
+        assert_eq!(Some(&Some("D".to_string())), row.get("status"));
+    }
+
+    #[test]
+    fn test_patches_matched_by_normalized_key() {
+        let mut diff_log = StateDiffLog::default();
+        let tables = TableStore::default();
+        tables.insert(
+            "account".to_string(),
+            LocalTable {
+                primary_key: vec!["id".to_string()],
+                unique_keys: vec![vec!["email".to_string()]],
+                ..LocalTable::default()
+            },
+        );
+
+        for query in [
+            "update account set balance = 1 where id = 1.0",
+            "update account set status = 'A' where id = '01'",
+            "update account set status = 'B' where email = 'ADA@EXAMPLE.COM '",
+            "update account set status = 'C' where id = 2",
+        ] {
+            get_diff(
+                &mut diff_log,
+                &tables,
+                None,
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        let divergences = DivergenceIndex::new(
+            ordered_divergences(&mut diff_log.get_mut("account").unwrap()),
+            // utf8mb4_general_ci, a PAD SPACE collation
+            Collations::from([("email".to_string(), 45)]),
+        );
+
+        assert!(divergences
+            .divergences()
+            .iter()
+            .all(|(_, divergence)| matches!(divergence, Divergence::Patch(..))));
+
+        let mut row = row("1", "NEW");
+        row.insert("email".to_string(), Some("ada@example.com".to_string()));
+        assert!(divergences.apply(&mut row, 0));
+
+        assert_eq!(Some(&Some("1".to_string())), row.get("balance"));
+        assert_eq!(Some(&Some("B".to_string())), row.get("status"));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::connection::Connection;
+use crate::materialization::StateDiffLog;
+use crate::mysql::accumulator::handshake_response::HandshakeResponseAccumulator;
+use dashmap::DashMap;
+use log::debug;
+use once_cell::sync::Lazy;
+use std::env;
+use std::sync::Arc;
+
+/**
+Diff logs partitioned by namespace, so that parallel test runs get isolated views of intercepted writes.
+DashMap<K, V>; where K : Namespace, V: StateDiffLog
+*/
+pub type Namespaces = Arc<DashMap<String, StateDiffLog>>;
+
+/// Namespace of connections which don't specify one.
+pub const DEFAULT_NAMESPACE: &str = "";
+
+/// Name of the connection attribute (or query attribute) selecting the namespace.
+static NAMESPACE_ATTRIBUTE: Lazy<String> = Lazy::new(|| {
+    env::var("NAMESPACE_ATTRIBUTE").unwrap_or_else(|_| "mocksysql_namespace".to_string())
+});
+
+/// Whether connections without the namespace attribute are namespaced by their username.
+static NAMESPACE_BY_USERNAME: Lazy<bool> =
+    Lazy::new(|| env::var("NAMESPACE_BY_USERNAME").is_ok_and(|val| val == "true"));
+
+/// Namespace of a connection, taken from its connection attributes, or its username if enabled.
+pub fn connection_namespace(handshake_response: &HandshakeResponseAccumulator) -> String {
+    match handshake_response.connection_attribute(&NAMESPACE_ATTRIBUTE) {
+        Some(namespace) => namespace.to_string(),
+        None if *NAMESPACE_BY_USERNAME => handshake_response.username().to_string(),
+        None => DEFAULT_NAMESPACE.to_string(),
+    }
+}
+
+/// Points the diff log of the connection at the namespace of its last command, i.e, the namespace query attribute
+/// if present, or the namespace of the connection otherwise.
+pub fn select_namespace(connection: &mut Connection) {
+    let namespace = connection
+        .get_last_command()
+        .and_then(|command| command.attributes.get(NAMESPACE_ATTRIBUTE.as_str()))
+        .unwrap_or(&connection.namespace)
+        .clone();
+
+    debug!("Using namespace {:?}", namespace);
+    connection.diff = diff_log(&connection.namespaces, &namespace);
+}
+
+pub fn diff_log(namespaces: &Namespaces, namespace: &str) -> StateDiffLog {
+    namespaces.entry(namespace.to_string()).or_default().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Connection;
+    use crate::materialization::namespace::{diff_log, select_namespace, DEFAULT_NAMESPACE};
+    use crate::mysql::command::{Command, MySqlCommand};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_select_namespace() {
+        let mut connection = Connection::default();
+        connection.namespace = "worker-1".to_string();
+
+        let mut command = Command::from_bytes(MySqlCommand::ComQuery, b"select 1");
+        connection.last_command = Some(command.clone());
+        select_namespace(&mut connection);
+        assert!(Arc::ptr_eq(
+            &connection.diff,
+            &diff_log(&connection.namespaces, "worker-1")
+        ));
+
+        command
+            .attributes
+            .insert("mocksysql_namespace".to_string(), "worker-2".to_string());
+        connection.last_command = Some(command);
+        select_namespace(&mut connection);
+        assert!(Arc::ptr_eq(
+            &connection.diff,
+            &diff_log(&connection.namespaces, "worker-2")
+        ));
+        assert!(!Arc::ptr_eq(
+            &connection.diff,
+            &diff_log(&connection.namespaces, DEFAULT_NAMESPACE)
+        ));
+    }
+}
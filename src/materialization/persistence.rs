@@ -0,0 +1,244 @@
+use crate::materialization::namespace::Namespaces;
+use crate::materialization::{
+    insert_divergence, local_table_key, Divergence, RecordedDivergence, TableStore,
+    DIVERGENCE_SEQUENCE,
+};
+use log::{error, info};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+use std::{env, fs, thread};
+
+/// File the diff logs are saved to and restored from, persistence is disabled if unset.
+static STATE_FILE: Lazy<Option<String>> = Lazy::new(|| env::var("STATE_FILE").ok());
+
+/// Directory files named by admin commands are resolved in. Only STATE_FILE can be used if unset.
+static ADMIN_FILE_DIR: Lazy<Option<String>> = Lazy::new(|| env::var("ADMIN_FILE_DIR").ok());
+
+/// Seconds between periodic saves of the state file, 0 disables periodic saves.
+static STATE_SAVE_INTERVAL: Lazy<u64> = Lazy::new(|| {
+    env::var("STATE_SAVE_INTERVAL")
+        .ok()
+        .and_then(|interval| interval.parse().ok())
+        .unwrap_or(60)
+});
+
+/// Serialized form of the diff logs of every namespace.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+    /// Sequence number of the next divergence, so that restored divergences keep their order.
+    next_sequence: u64,
+    /// Namespace -> table key -> (sequence number, divergence)
+    namespaces: HashMap<String, HashMap<String, Vec<(u64, RecordedDivergence)>>>,
+}
+
+/// Resolves the file used by snapshot and restore commands, falling back to `STATE_FILE`.
+pub fn state_file(path: Option<&str>) -> Result<String, String> {
+    match path {
+        Some(path) => admin_file(path),
+        None => STATE_FILE
+            .clone()
+            .ok_or_else(|| "No file specified and STATE_FILE is not set".to_string()),
+    }
+}
+
+/// Resolves a file named by an admin command inside `ADMIN_FILE_DIR`, as any client of the proxy can name one.
+pub fn admin_file(path: &str) -> Result<String, String> {
+    resolve_admin_file(ADMIN_FILE_DIR.as_deref(), path)
+}
+
+fn resolve_admin_file(directory: Option<&str>, path: &str) -> Result<String, String> {
+    let directory = directory.ok_or("Files can only be named if ADMIN_FILE_DIR is set")?;
+
+    // Absolute paths and `..` would reach outside of the directory.
+    let relative = Path::new(path);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(format!(
+            "{} is not a relative path inside ADMIN_FILE_DIR",
+            path
+        ));
+    }
+
+    Ok(Path::new(directory)
+        .join(relative)
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// Writes the diff logs of every namespace to `path`, returning the number of divergences saved.
+pub fn snapshot(namespaces: &Namespaces, path: &str) -> Result<usize, String> {
+    let mut snapshot = Snapshot {
+        next_sequence: DIVERGENCE_SEQUENCE.load(Ordering::Relaxed),
+        ..Snapshot::default()
+    };
+    let mut count = 0;
+
+    for namespace in namespaces.iter() {
+        let tables = snapshot
+            .namespaces
+            .entry(namespace.key().clone())
+            .or_default();
+
+        for mut entry in namespace.value().iter_mut() {
+            let table_name = entry.key().clone();
+            let divergences: Vec<(u64, RecordedDivergence)> = entry
+                .value_mut()
+                .iter()
+                .map(|(sequence, recorded)| (*sequence, recorded.clone()))
+                .collect();
+
+            count += divergences.len();
+            tables.insert(table_name, divergences);
+        }
+    }
+
+    let json = serde_json::to_string(&snapshot).map_err(|e| e.to_string())?;
+
+    // Written to a temporary file first, so that a crash mid-write doesn't corrupt the previous snapshot.
+    let temporary_path = format!("{}.tmp", path);
+    fs::write(&temporary_path, json).map_err(|e| e.to_string())?;
+    fs::rename(&temporary_path, path).map_err(|e| e.to_string())?;
+
+    Ok(count)
+}
+
+/// Replaces the diff logs of every namespace with the ones saved in `path`, returning the number of divergences
+/// restored. Divergences which expired in the meantime are dropped.
+pub fn restore(namespaces: &Namespaces, tables: &TableStore, path: &str) -> Result<usize, String> {
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let snapshot: Snapshot = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+    DIVERGENCE_SEQUENCE.fetch_max(snapshot.next_sequence, Ordering::Relaxed);
+
+    // Connections hold on to the diff log of their namespace, so logs are cleared in place rather than replaced.
+    for namespace in namespaces.iter() {
+        namespace.value().clear();
+    }
+
+    let now = SystemTime::now();
+    let mut count = 0;
+
+    for (namespace, diff_tables) in snapshot.namespaces {
+        let diff = namespaces.entry(namespace).or_default().clone();
+
+        for (table_name, divergences) in diff_tables {
+            for (sequence, recorded) in divergences {
+                let ttl = match recorded.expires_at.duration_since(now) {
+                    Ok(ttl) if ttl > Duration::ZERO => ttl,
+                    _ => continue,
+                };
+
+                // Keep generated ids from colliding with the ones handed out before the restore.
+                if let Divergence::Insert(_, Some(id)) = &recorded.divergence {
+                    let mut table = tables
+                        .entry(local_table_key(tables, &table_name))
+                        .or_default();
+                    table.next_auto_increment = table.next_auto_increment.max(Some(id + 1));
+                }
+
+                insert_divergence(&diff, &table_name, sequence, recorded.divergence, ttl);
+                count += 1;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// Restores the state file at startup, if one is configured, and starts saving it periodically.
+pub fn load_state_file(namespaces: &Namespaces, tables: &TableStore) {
+    let path = match STATE_FILE.as_ref() {
+        Some(path) => path.clone(),
+        None => return,
+    };
+
+    if fs::metadata(&path).is_ok() {
+        match restore(namespaces, tables, &path) {
+            Ok(count) => info!("Restored {} divergences from {}", count, path),
+            Err(e) => error!("Unable to restore state from {}: {}", path, e),
+        }
+    }
+
+    if *STATE_SAVE_INTERVAL == 0 {
+        return;
+    }
+
+    let namespaces = namespaces.clone();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(*STATE_SAVE_INTERVAL));
+
+        if let Err(e) = snapshot(&namespaces, &path) {
+            error!("Unable to save state to {}: {}", path, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::materialization::namespace::{diff_log, Namespaces};
+    use crate::materialization::persistence::{resolve_admin_file, restore, snapshot};
+    use crate::materialization::{get_diff, ordered_divergences, Divergence, TableStore};
+    use crate::mysql::command::{Command, MySqlCommand};
+    use std::env;
+
+    #[test]
+    fn test_admin_file_confined() {
+        assert_eq!(
+            Ok("/tmp/states/after-setup.json".to_string()),
+            resolve_admin_file(Some("/tmp/states"), "after-setup.json")
+        );
+
+        for path in ["/etc/passwd", "../passwd", "states/../../passwd"] {
+            assert!(
+                resolve_admin_file(Some("/tmp/states"), path).is_err(),
+                "{}",
+                path
+            );
+        }
+        assert!(resolve_admin_file(None, "after-setup.json").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let namespaces = Namespaces::default();
+        let tables = TableStore::default();
+        let path = env::temp_dir().join(format!("mocksysql-state-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        for query in [
+            "update account set balance = balance + 1 where id in (1, 2)",
+            "delete from account where status = 'CLOSED'",
+        ] {
+            get_diff(
+                &mut diff_log(&namespaces, "worker-1"),
+                &tables,
+                Some("shop"),
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        assert_eq!(Ok(2), snapshot(&namespaces, path));
+
+        let diff = diff_log(&namespaces, "worker-1");
+        diff.clear();
+        assert_eq!(Ok(2), restore(&namespaces, &tables, path));
+        std::fs::remove_file(path).unwrap();
+
+        let divergences = ordered_divergences(&mut diff.get_mut("shop.account").unwrap());
+        assert_eq!(2, divergences.len());
+        match &divergences[0].1 {
+            Divergence::Update(Some(selection), assignments) => {
+                assert_eq!("id IN (1, 2)", selection.to_string());
+                assert_eq!("balance + 1", assignments[0].1.to_string());
+            }
+            divergence => panic!("Unexpected divergence {:?}", divergence),
+        }
+        assert!(matches!(divergences[1].1, Divergence::Delete(Some(_))));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::materialization::{
+    auto_increment, insert_rows, literal_value, table_key, unqualified_name, LocalTable, TableStore,
+};
+use log::{debug, error, info};
+use sqlparser::ast::{ColumnOption, CreateTable, Statement, TableConstraint, TableObject, Use};
+use sqlparser::dialect::MySqlDialect;
+use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::env;
+use std::fs;
+
+/// Loads the SQL dump files listed in `SEED_FILES` (comma separated) into the table store.
+pub fn load_seed_files(tables: &TableStore) {
+    if let Ok(files) = env::var("SEED_FILES") {
+        for path in files.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match fs::read_to_string(path) {
+                Ok(sql) => {
+                    let rows = seed_from_sql(tables, &sql);
+                    info!("Seeded {} rows from {}", rows, path);
+                }
+                Err(e) => error!("Unable to read seed file {}: {}", path, e),
+            }
+        }
+    }
+}
+
+/// Applies the CREATE TABLE and INSERT statements in `sql` to the table store, returning the number of rows seeded.
+/// Statements are parsed one at a time so that a dump containing unsupported statements can still be loaded.
+/// Tables are keyed by the database selected through `USE` (as emitted by mysqldump), and by bare name otherwise.
+pub fn seed_from_sql(tables: &TableStore, sql: &str) -> usize {
+    let dialect = MySqlDialect {};
+    let tokens = match Tokenizer::new(&dialect, sql).tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            error!("Unable to tokenize seed file: {}", e);
+            return 0;
+        }
+    };
+
+    let mut seeded_rows = 0;
+    let mut database: Option<String> = None;
+
+    for statement_tokens in tokens.split(|token| *token == Token::SemiColon) {
+        if statement_tokens
+            .iter()
+            .all(|token| matches!(token, Token::Whitespace(_)))
+        {
+            continue;
+        }
+
+        let statement = Parser::new(&dialect)
+            .with_tokens(statement_tokens.to_vec())
+            .parse_statement();
+
+        match statement {
+            Ok(Statement::Use(Use::Object(name) | Use::Database(name) | Use::Schema(name))) => {
+                database = Some(unqualified_name(&name));
+            }
+            Ok(Statement::CreateTable(create_table)) => {
+                let table = local_table_from_definition(&create_table);
+                tables.insert(table_key(&create_table.name, database.as_deref()), table);
+            }
+            Ok(Statement::Insert(insert)) => {
+                let table_name = match &insert.table {
+                    TableObject::TableName(name) => table_key(name, database.as_deref()),
+                    _ => continue,
+                };
+
+                let mut table = tables.entry(table_name.clone()).or_default();
+
+                match insert_rows(&insert, Some(&*table)) {
+                    Ok(mut rows) => {
+                        seeded_rows += rows.len();
+                        for row in rows.iter_mut() {
+                            auto_increment::assign_id(&mut table, row);
+                        }
+                        table.rows.extend(rows);
+                    }
+                    Err(e) => error!("Skipping seed rows for {}: {}", table_name, e),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => debug!("Skipping unsupported statement in seed file: {}", e),
+        }
+    }
+
+    seeded_rows
+}
+
+fn local_table_from_definition(create_table: &CreateTable) -> LocalTable {
+    let mut table = LocalTable::default();
+
+    for column in &create_table.columns {
+        let column_name = column.name.value.clone();
+        let mut default = None;
+
+        for option in &column.options {
+            match &option.option {
+                ColumnOption::Unique {
+                    is_primary: true, ..
+                } => table.primary_key.push(column_name.clone()),
+                ColumnOption::Unique {
+                    is_primary: false, ..
+                } => table.unique_keys.push(vec![column_name.clone()]),
+                ColumnOption::DialectSpecific(tokens)
+                    if tokens.contains(&Token::make_keyword("AUTO_INCREMENT")) =>
+                {
+                    table.auto_increment = Some(column_name.clone())
+                }
+                ColumnOption::Default(expr) => default = literal_value(expr).ok().flatten(),
+                _ => {}
+            }
+        }
+
+        table.defaults.insert(column_name.clone(), default);
+        table.columns.push(column_name);
+    }
+
+    if table.auto_increment.is_some() {
+        table.next_auto_increment = Some(create_table.auto_increment_offset.unwrap_or(1) as u64);
+    }
+
+    for constraint in &create_table.constraints {
+        match constraint {
+            TableConstraint::PrimaryKey { columns, .. } => {
+                table.primary_key = columns.iter().map(|c| c.value.clone()).collect()
+            }
+            TableConstraint::Unique { columns, .. } => table
+                .unique_keys
+                .push(columns.iter().map(|c| c.value.clone()).collect()),
+            _ => {}
+        }
+    }
+
+    // The definition is authoritative, the table may not even exist upstream.
+    table.keys_synced = true;
+    table.auto_increment_synced = true;
+    table.local_only = true;
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::materialization::seed::seed_from_sql;
+    use crate::materialization::TableStore;
+
+    #[test]
+    fn test_seed_from_dump() {
+        let tables = TableStore::default();
+
+        let seeded = seed_from_sql(
+            &tables,
+            "/*!40101 SET NAMES utf8mb4 */;
+            DROP TABLE IF EXISTS `account`;
+            CREATE TABLE `account` (
+              `id` int NOT NULL AUTO_INCREMENT,
+              `status` varchar(16) DEFAULT 'NEW',
+              `balance` int DEFAULT NULL,
+              `email` varchar(64) DEFAULT NULL,
+              PRIMARY KEY (`id`),
+              UNIQUE KEY `uniq_email` (`email`)
+            ) ENGINE=InnoDB AUTO_INCREMENT=3 DEFAULT CHARSET=utf8mb4;
+            LOCK TABLES `account` WRITE;
+            INSERT INTO `account` VALUES (1,'ACTIVE',-20,NULL),(2,'CLOSED; FOR GOOD',NULL,NULL);
+            INSERT INTO `account` (`id`) VALUES (3);
+            UNLOCK TABLES;",
+        );
+
+        assert_eq!(3, seeded);
+
+        let table = tables.get("account").unwrap();
+        assert_eq!(vec!["id", "status", "balance", "email"], table.columns);
+        assert_eq!(vec!["id"], table.primary_key);
+        assert_eq!(vec![vec!["email"]], table.unique_keys);
+        assert!(table.keys_synced && table.local_only);
+        assert_eq!(Some("id".to_string()), table.auto_increment);
+        assert_eq!(Some(4), table.next_auto_increment);
+        assert_eq!(
+            Some(Some("-20".to_string())),
+            table.rows[0].get("balance").cloned()
+        );
+        assert_eq!(
+            Some(Some("CLOSED; FOR GOOD".to_string())),
+            table.rows[1].get("status").cloned()
+        );
+        assert_eq!(
+            Some(Some("NEW".to_string())),
+            table.rows[2].get("status").cloned()
+        );
+    }
+}
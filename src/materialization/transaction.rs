@@ -0,0 +1,360 @@
+use crate::materialization::{
+    update_diff_log, Divergence, StateDiffLog, StateDifference, DIVERGENCE_SEQUENCE,
+};
+use crate::mysql::command::{Command, MySqlCommand};
+use crate::mysql::packet::ServerStatusFlags;
+use log::debug;
+use sqlparser::ast::{Expr, ObjectName, Set, Statement, Value};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Transaction state of a connection. Divergences recorded inside a transaction (or while autocommit is disabled)
+/// are buffered, visible only to the connection, until they are merged into the shared [`StateDiffLog`] on COMMIT
+/// or discarded on ROLLBACK.
+pub struct Transaction {
+    /// Whether a transaction was explicitly started with BEGIN or START TRANSACTION.
+    pub active: bool,
+    pub autocommit: bool,
+    /// Buffered divergences along with the diff log they are committed to, i.e, the namespace of the command which
+    /// recorded them rather than the namespace of the COMMIT.
+    pending: Vec<(StateDiffLog, StateDiffLog)>,
+    /// Savepoints along with the last sequence number recorded before each of them, oldest first.
+    savepoints: Vec<(String, u64)>,
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Transaction {
+            active: false,
+            autocommit: true,
+            pending: vec![],
+            savepoints: vec![],
+        }
+    }
+}
+
+impl Transaction {
+    /// Whether divergences should be buffered rather than recorded in the shared log.
+    pub fn buffering(&self) -> bool {
+        self.active || !self.autocommit
+    }
+
+    /// Divergences buffered for the diff log.
+    pub fn pending(&mut self, diff: &StateDiffLog) -> &mut StateDiffLog {
+        let index = match self.pending_index(diff) {
+            Some(index) => index,
+            None => {
+                self.pending.push((diff.clone(), StateDiffLog::default()));
+                self.pending.len() - 1
+            }
+        };
+        &mut self.pending[index].1
+    }
+
+    /// Divergences buffered for the diff log, if any.
+    pub fn pending_in(&self, diff: &StateDiffLog) -> Option<&StateDiffLog> {
+        self.pending_index(diff).map(|index| &self.pending[index].1)
+    }
+
+    fn pending_index(&self, diff: &StateDiffLog) -> Option<usize> {
+        self.pending
+            .iter()
+            .position(|(target, _)| Arc::ptr_eq(target, diff))
+    }
+
+    /// Status flags reported to the client, as of a write buffered (or not) by the transaction.
+    pub fn status_flags(&self) -> u16 {
+        let mut status_flags = 0;
+
+        if self.buffering() {
+            status_flags |= ServerStatusFlags::ServerStatusInTrans as u16;
+        }
+        if self.autocommit {
+            status_flags |= ServerStatusFlags::ServerStatusAutocommit as u16;
+        }
+
+        status_flags
+    }
+
+    /// Applies the transaction control statements of a command which completed successfully.
+    pub fn track(&mut self, command: &Command) {
+        if command.com_code == MySqlCommand::ComResetConnection {
+            self.rollback();
+            self.autocommit = true;
+            return;
+        }
+
+        if command.com_code != MySqlCommand::ComQuery {
+            return;
+        }
+
+        for statement in command.ast.iter().flatten() {
+            match statement {
+                // Starting a transaction implicitly commits the ongoing one.
+                Statement::StartTransaction { .. } => {
+                    self.commit();
+                    self.active = true;
+                }
+                Statement::Commit { .. } => self.commit(),
+                // So do DDL statements and LOCK TABLES. Other statements MySQL commits implicitly (e.g, account
+                // management) aren't tracked.
+                Statement::CreateTable(_)
+                | Statement::CreateIndex(_)
+                | Statement::AlterTable { .. }
+                | Statement::Drop { .. }
+                | Statement::Truncate { .. }
+                | Statement::RenameTable(_)
+                | Statement::LockTables { .. } => self.commit(),
+                Statement::Rollback {
+                    savepoint: Some(name),
+                    ..
+                } => self.rollback_to(&name.value),
+                Statement::Rollback { .. } => self.rollback(),
+                Statement::Savepoint { name } => {
+                    self.savepoints
+                        .retain(|(savepoint, _)| *savepoint != name.value);
+                    self.savepoints.push((
+                        name.value.clone(),
+                        DIVERGENCE_SEQUENCE.load(Ordering::Relaxed) - 1,
+                    ));
+                }
+                Statement::ReleaseSavepoint { name } => {
+                    if let Some(index) = self.savepoint_index(&name.value) {
+                        self.savepoints.truncate(index);
+                    }
+                }
+                Statement::Set(set) => {
+                    if let Some(autocommit) = autocommit_value(set) {
+                        // Enabling autocommit commits the ongoing transaction.
+                        if autocommit && !self.autocommit {
+                            self.commit();
+                        }
+                        self.autocommit = autocommit;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Merges the pending divergences into the shared logs they were recorded for. They are given fresh sequence
+    /// numbers, so that they are layered on top of the divergences committed by other connections in the meantime.
+    pub fn commit(&mut self) {
+        let mut divergences: Vec<(u64, usize, String, Divergence)> = vec![];
+
+        for (index, (_, pending)) in self.pending.iter().enumerate() {
+            for mut entry in pending.iter_mut() {
+                let table_name = entry.key().clone();
+                divergences.extend(entry.value_mut().iter().map(|(sequence, recorded)| {
+                    (
+                        *sequence,
+                        index,
+                        table_name.clone(),
+                        recorded.divergence.clone(),
+                    )
+                }));
+            }
+        }
+
+        divergences.sort_by_key(|(sequence, _, _, _)| *sequence);
+        debug!("Committing {} pending divergences", divergences.len());
+
+        for (_, index, table_name, divergence) in divergences {
+            update_diff_log(&mut self.pending[index].0, &table_name, divergence);
+        }
+
+        self.rollback();
+    }
+
+    /// Discards the pending divergences and ends the transaction.
+    pub fn rollback(&mut self) {
+        self.pending.clear();
+        self.savepoints.clear();
+        self.active = false;
+    }
+
+    /// Discards the divergences recorded after the savepoint, keeping the savepoint itself.
+    fn rollback_to(&mut self, name: &str) {
+        let index = match self.savepoint_index(name) {
+            Some(index) => index,
+            None => return,
+        };
+
+        let since = self.savepoints[index].1;
+        self.savepoints.truncate(index + 1);
+
+        for mut entry in self
+            .pending
+            .iter()
+            .flat_map(|(_, pending)| pending.iter_mut())
+        {
+            let state_difference: &mut StateDifference = entry.value_mut();
+            let discarded: Vec<u64> = state_difference
+                .iter()
+                .map(|(sequence, _)| *sequence)
+                .filter(|sequence| *sequence > since)
+                .collect();
+
+            for sequence in discarded {
+                state_difference.remove(&sequence);
+            }
+        }
+    }
+
+    fn savepoint_index(&self, name: &str) -> Option<usize> {
+        self.savepoints
+            .iter()
+            .position(|(savepoint, _)| savepoint.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Value assigned to `autocommit` by a SET statement, if any.
+fn autocommit_value(set: &Set) -> Option<bool> {
+    let assignments: Vec<(&ObjectName, &Expr)> = match set {
+        Set::SingleAssignment {
+            variable, values, ..
+        } => values
+            .first()
+            .map(|value| (variable, value))
+            .into_iter()
+            .collect(),
+        Set::MultipleAssignments { assignments } => assignments
+            .iter()
+            .map(|assignment| (&assignment.name, &assignment.value))
+            .collect(),
+        _ => vec![],
+    };
+
+    let (_, value) = assignments.into_iter().rev().find(|(name, _)| {
+        name.to_string()
+            .trim_start_matches('@')
+            .rsplit('.')
+            .next()
+            .is_some_and(|name| name.eq_ignore_ascii_case("autocommit"))
+    })?;
+
+    let value = match value {
+        Expr::Value(value) => match &value.value {
+            Value::Boolean(b) => return Some(*b),
+            Value::Number(n, _) => n.clone(),
+            Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => s.clone(),
+            _ => return None,
+        },
+        Expr::Identifier(ident) => ident.value.clone(),
+        _ => return None,
+    };
+
+    match value.to_uppercase().as_str() {
+        "1" | "ON" => Some(true),
+        "0" | "OFF" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::materialization::transaction::Transaction;
+    use crate::materialization::{divergence_count, get_diff, StateDiffLog, TableStore};
+    use crate::mysql::command::{Command, MySqlCommand};
+
+    fn run(transaction: &mut Transaction, diff: &mut StateDiffLog, query: &str) {
+        let command = Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes());
+
+        let target = match transaction.buffering() {
+            true => transaction.pending(diff),
+            false => diff,
+        };
+        get_diff(target, &TableStore::default(), Some("shop"), &command.ast);
+
+        transaction.track(&command);
+    }
+
+    fn pending_count(transaction: &Transaction) -> usize {
+        transaction
+            .pending
+            .iter()
+            .map(|(_, pending)| divergence_count(pending))
+            .sum()
+    }
+
+    #[test]
+    fn test_transaction_buffering() {
+        let mut diff = StateDiffLog::default();
+        let mut transaction = Transaction::default();
+
+        run(&mut transaction, &mut diff, "BEGIN");
+        run(
+            &mut transaction,
+            &mut diff,
+            "update account set status = 'A'",
+        );
+        run(&mut transaction, &mut diff, "SAVEPOINT before_delete");
+        run(&mut transaction, &mut diff, "delete from account");
+        assert_eq!(2, pending_count(&transaction));
+        assert!(diff.is_empty());
+
+        run(
+            &mut transaction,
+            &mut diff,
+            "ROLLBACK TO SAVEPOINT before_delete",
+        );
+        assert_eq!(1, pending_count(&transaction));
+
+        run(&mut transaction, &mut diff, "COMMIT");
+        assert!(!transaction.buffering());
+        assert_eq!(0, pending_count(&transaction));
+        assert_eq!(1, divergence_count(&diff));
+
+        run(&mut transaction, &mut diff, "SET autocommit = 0");
+        run(&mut transaction, &mut diff, "delete from account");
+        run(&mut transaction, &mut diff, "ROLLBACK");
+        assert_eq!(0, pending_count(&transaction));
+        assert_eq!(1, divergence_count(&diff));
+        assert!(transaction.buffering());
+    }
+
+    #[test]
+    fn test_pending_committed_to_their_namespace() {
+        let mut default_namespace = StateDiffLog::default();
+        let mut other_namespace = StateDiffLog::default();
+        let mut transaction = Transaction::default();
+
+        run(&mut transaction, &mut default_namespace, "BEGIN");
+        run(
+            &mut transaction,
+            &mut other_namespace,
+            "delete from account",
+        );
+        run(&mut transaction, &mut default_namespace, "COMMIT");
+
+        assert!(default_namespace.is_empty());
+        assert_eq!(1, divergence_count(&other_namespace));
+    }
+
+    #[test]
+    fn test_implicit_commit() {
+        let mut diff = StateDiffLog::default();
+        let mut transaction = Transaction::default();
+
+        run(&mut transaction, &mut diff, "BEGIN");
+        run(&mut transaction, &mut diff, "delete from account");
+        run(&mut transaction, &mut diff, "CREATE TABLE audit (id int)");
+        run(&mut transaction, &mut diff, "ROLLBACK");
+
+        assert!(!transaction.buffering());
+        assert_eq!(1, divergence_count(&diff));
+    }
+
+    #[test]
+    fn test_status_flags() {
+        let mut diff = StateDiffLog::default();
+        let mut transaction = Transaction::default();
+        assert_eq!(0x02, transaction.status_flags());
+
+        run(&mut transaction, &mut diff, "START TRANSACTION");
+        assert_eq!(0x03, transaction.status_flags());
+
+        run(&mut transaction, &mut diff, "SET autocommit = 0");
+        assert_eq!(0x01, transaction.status_flags());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::connection::{Connection, Phase};
+use crate::connection_handler::{read_bytes, write_bytes};
+use crate::materialization::Row;
+use crate::mysql::accumulator::result_set::{parse_text_row, ColumnDefinition};
+use crate::mysql::accumulator::CapabilityFlags;
+use crate::mysql::packet::{Packet, PacketHeader, PacketType};
+use crate::mysql::types::{Converter, IntLenEnc, StringEOFEnc};
+use log::debug;
+
+/// Text result set of a query issued by the proxy itself on the upstream connection.
+#[derive(Debug, Default)]
+pub struct QueryResult {
+    pub columns: Vec<ColumnDefinition>,
+    pub rows: Vec<Row>,
+}
+
+/// Executes `sql` on the upstream server using the authenticated connection of the client.
+/// Must only be called while the connection is in the command phase, i.e, no response is pending from the server.
+pub fn query(connection: &mut Connection, sql: &str) -> Result<QueryResult, String> {
+    let capability_flags = match connection.negotiated_flags() {
+        Some(capability_flags) => capability_flags,
+        None => return Err("Handshake not completed".to_string()),
+    };
+
+    debug!("Querying upstream: {}", sql);
+
+    let mut body = vec![0x03];
+    if capability_flags & CapabilityFlags::ClientQueryAttributes as u32 != 0 {
+        body.extend(IntLenEnc::encode(0, None)); // parameter_count
+        body.extend(IntLenEnc::encode(1, None)); // parameter_set_count
+    }
+    body.extend(sql.as_bytes());
+
+    let packet = Packet {
+        header: PacketHeader {
+            size: body.len(),
+            seq: 0,
+        },
+        body,
+        p_type: PacketType::Command,
+        skip: false,
+    };
+
+    write_bytes(&mut connection.server_connection, &packet.to_bytes());
+
+    let mut result_set = ResultSetReader::new(capability_flags);
+    let mut buffer: Vec<u8> = vec![];
+    let mut buf: [u8; 4096] = [0; 4096];
+
+    loop {
+        let read =
+            read_bytes(&mut connection.server_connection, &mut buf).map_err(|e| e.to_string())?;
+
+        if read == 0 {
+            return Err("Upstream closed the connection".to_string());
+        }
+
+        buffer.extend_from_slice(&buf[..read]);
+
+        while let Ok(packet) = Packet::from_bytes(&buffer, Phase::PendingResponse) {
+            buffer.drain(..4 + packet.header.size);
+
+            if result_set.consume(&packet)? {
+                return Ok(result_set.result);
+            }
+        }
+    }
+}
+
+/// Quotes `value` as a string literal for a query issued upstream, escaping quotes and backslashes.
+pub fn string_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+}
+
+/// Quotes `name` as an identifier for a query issued upstream, escaping backticks.
+pub fn quoted_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+struct ResultSetReader {
+    capability_flags: u32,
+    column_count: Option<usize>,
+    columns_hydrated: bool,
+    result: QueryResult,
+}
+
+impl ResultSetReader {
+    fn new(capability_flags: u32) -> ResultSetReader {
+        ResultSetReader {
+            capability_flags,
+            column_count: None,
+            columns_hydrated: false,
+            result: QueryResult::default(),
+        }
+    }
+
+    /// Consumes a packet of the response, returning true once the result set is complete.
+    fn consume(&mut self, packet: &Packet) -> Result<bool, String> {
+        if packet.body.first() == Some(&0xff) {
+            // Error code, followed by the SQL state marker and SQL state with CLIENT_PROTOCOL_41.
+            let offset = match self.capability_flags & CapabilityFlags::ClientProtocol41 as u32 {
+                0 => 3,
+                _ => 9,
+            };
+            let message = packet.body.get(offset..).unwrap_or_default();
+            return Err(StringEOFEnc::from_bytes(&message.to_vec(), None).result);
+        }
+
+        let column_count = match self.column_count {
+            None => {
+                if packet.body.first() == Some(&0x00) {
+                    // OK packet, statement returned no result set.
+                    return Ok(true);
+                }
+
+                let mut offset = 0;
+                if self.capability_flags & CapabilityFlags::ClientOptionalResultSetMetadata as u32
+                    != 0
+                {
+                    offset += 1; // metadata_follows, always RESULTSET_METADATA_FULL
+                }
+
+                let count = IntLenEnc::from_bytes(&packet.body[offset..].to_vec(), None).result;
+                self.column_count = Some(count as usize);
+                return Ok(false);
+            }
+            Some(column_count) => column_count,
+        };
+
+        if self.result.columns.len() < column_count {
+            self.result
+                .columns
+                .push(ColumnDefinition::from_packet(packet));
+
+            if self.result.columns.len() == column_count
+                && self.capability_flags & CapabilityFlags::ClientDeprecateEof as u32 != 0
+            {
+                self.columns_hydrated = true;
+            }
+            return Ok(false);
+        }
+
+        if !self.columns_hydrated {
+            // EOF packet following column definitions.
+            self.columns_hydrated = true;
+            return Ok(false);
+        }
+
+        if packet.body.first() == Some(&0xfe) {
+            // EOF (or OK when CLIENT_DEPRECATE_EOF is set) terminating the rows.
+            // A row can't begin with 0xfe, as it would denote a value larger than the maximum packet size.
+            return Ok(true);
+        }
+
+        self.result
+            .rows
+            .push(parse_text_row(&packet.body, &self.result.columns));
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Phase;
+    use crate::materialization::upstream::{quoted_identifier, string_literal, ResultSetReader};
+    use crate::mysql::accumulator::CapabilityFlags;
+    use crate::mysql::packet::Packet;
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!("'it''s a \\\\ test'", string_literal("it's a \\ test"));
+        assert_eq!("`odd``name`", quoted_identifier("odd`name"));
+    }
+
+    #[test]
+    fn test_short_error_packet() {
+        let packet = Packet::from_bytes(
+            &[0x03, 0x00, 0x00, 0x01, 0xff, 0x15, 0x04],
+            Phase::PendingResponse,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(String::new()),
+            ResultSetReader::new(CapabilityFlags::ClientProtocol41 as u32).consume(&packet)
+        );
+    }
+
+    #[test]
+    fn test_result_set_reader() {
+        let packets: Vec<&[u8]> = vec![
+            &[0x01, 0x00, 0x00, 0x01, 0x01],
+            &[
+                0x20, 0x00, 0x00, 0x02, 0x03, 0x64, 0x65, 0x66, 0x04, 0x74, 0x65, 0x73, 0x74, 0x01,
+                0x74, 0x01, 0x74, 0x02, 0x69, 0x64, 0x02, 0x69, 0x64, 0x0c, 0x3f, 0x00, 0x0b, 0x00,
+                0x00, 0x00, 0x03, 0x03, 0x42, 0x00, 0x00, 0x00,
+            ],
+            &[0x05, 0x00, 0x00, 0x03, 0xfe, 0x00, 0x00, 0x02, 0x00],
+            &[0x02, 0x00, 0x00, 0x04, 0x01, 0x37],
+            &[0x01, 0x00, 0x00, 0x05, 0xfb],
+            &[0x05, 0x00, 0x00, 0x06, 0xfe, 0x00, 0x00, 0x02, 0x00],
+        ];
+
+        let mut reader = ResultSetReader::new(0);
+        let mut complete = false;
+
+        for bytes in packets {
+            let packet = Packet::from_bytes(bytes, Phase::PendingResponse).unwrap();
+            complete = reader.consume(&packet).unwrap();
+        }
+
+        assert!(complete);
+        assert_eq!(1, reader.result.columns.len());
+        assert_eq!(2, reader.result.rows.len());
+        assert_eq!(
+            Some(&Some("7".to_string())),
+            reader.result.rows[0].get("id")
+        );
+        assert_eq!(Some(&None), reader.result.rows[1].get("id"));
+    }
+}
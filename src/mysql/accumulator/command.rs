@@ -3,8 +3,9 @@ use crate::mysql::accumulator::result_set::ResponseAccumulator;
 use crate::mysql::accumulator::{AccumulationDelta, Accumulator, CapabilityFlags};
 use crate::mysql::command::{Command, MySqlCommand};
 use crate::mysql::packet::Packet;
-use crate::mysql::types::{Converter, IntLenEnc};
+use crate::mysql::types::{Converter, DecodeResult, IntFixedLen, IntLenEnc, StringLenEnc};
 use log::debug;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Default)]
 pub struct CommandAccumulator {
@@ -14,7 +15,6 @@ pub struct CommandAccumulator {
     #[allow(dead_code)]
     null_bitmap: Option<Vec<u8>>,
     new_params_bind_flag: u8,
-    #[allow(dead_code)]
     parameters: Option<Vec<Param>>,
     accumulation_complete: bool,
     #[allow(dead_code)]
@@ -23,9 +23,7 @@ pub struct CommandAccumulator {
 
 #[derive(Debug, Clone, Default)]
 struct Param {
-    #[allow(dead_code)]
     param_type_and_flag: u16,
-    #[allow(dead_code)]
     parameter_name: String,
 }
 
@@ -33,6 +31,7 @@ impl Accumulator for CommandAccumulator {
     fn consume(&mut self, packet: &mut Packet, connection: &Connection) -> Phase {
         let mut offset = 0;
         let body = &packet.body;
+        let mut attributes = HashMap::new();
 
         if *body.first().unwrap() == 0x03 {
             // COM_QUERY
@@ -57,26 +56,62 @@ impl Accumulator for CommandAccumulator {
                 assert_eq!(self.parameter_set_count.unwrap(), 0x01);
 
                 if self.parameter_count.unwrap() > 0 {
-                    // FIXME: Binary decoding not implemented
-                    offset += self.parameter_count.unwrap().div_ceil(8);
+                    let parameter_count = self.parameter_count.unwrap();
+
+                    let null_bitmap = body[offset..offset + parameter_count.div_ceil(8)].to_vec();
+                    offset += null_bitmap.len();
 
                     self.new_params_bind_flag = {
-                        let result = IntLenEnc::from_bytes(&body[offset..].to_vec(), Some(1));
+                        let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(1));
                         offset += result.offset_increment;
-                        let _ = offset; // For future use
                         result.result as u8
                     };
 
-                    assert_eq!(self.parameter_set_count.unwrap(), 0x01);
-                    todo!("Implementing changes for binary protocol");
+                    // Always set for COM_QUERY, as query attributes are not bound ahead of time.
+                    assert_eq!(self.new_params_bind_flag, 0x01);
+
+                    let mut parameters = vec![];
+                    for _ in 0..parameter_count {
+                        let param_type_and_flag = {
+                            let result = IntFixedLen::from_bytes(&body[offset..].to_vec(), Some(2));
+                            offset += result.offset_increment;
+                            result.result as u16
+                        };
+
+                        let parameter_name = {
+                            let result = StringLenEnc::from_bytes(&body[offset..].to_vec(), None);
+                            offset += result.offset_increment;
+                            result.result
+                        };
+
+                        parameters.push(Param {
+                            param_type_and_flag,
+                            parameter_name,
+                        });
+                    }
+
+                    for (i, param) in parameters.iter().enumerate() {
+                        if null_bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                            continue;
+                        }
+
+                        let result =
+                            decode_binary_value(&body[offset..], param.param_type_and_flag);
+                        offset += result.offset_increment;
+                        attributes.insert(param.parameter_name.clone(), result.result);
+                    }
+
+                    self.null_bitmap = Some(null_bitmap);
+                    self.parameters = Some(parameters);
                 }
             }
         }
 
-        let command = Command::from_bytes(
+        let mut command = Command::from_bytes(
             MySqlCommand::from_byte(packet.body[0]).unwrap(),
             &packet.body[offset..],
         );
+        command.attributes = attributes;
 
         let next_phase = match command.com_code {
             MySqlCommand::ComStmtClose => Phase::Command,
@@ -101,3 +136,131 @@ impl Accumulator for CommandAccumulator {
         })
     }
 }
+
+/// Decodes a value of the binary protocol into its text representation.
+/// https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_binary_resultset.html#sect_protocol_binary_resultset_row_value
+fn decode_binary_value(body: &[u8], param_type_and_flag: u16) -> DecodeResult<String> {
+    let unsigned = param_type_and_flag & 0x8000 != 0;
+
+    let integer = |length: usize| {
+        let value = IntFixedLen::from_bytes(&body[..length].to_vec(), Some(length)).result;
+        let shift = 64 - 8 * length as u32;
+        let result = match unsigned {
+            true => value.to_string(),
+            false => (((value << shift) as i64) >> shift).to_string(),
+        };
+
+        DecodeResult {
+            result,
+            offset_increment: length,
+        }
+    };
+
+    match param_type_and_flag & 0xff {
+        0x06 => DecodeResult {
+            result: String::new(),
+            offset_increment: 0,
+        },
+        0x01 => integer(1),
+        0x02 | 0x0d => integer(2),
+        0x03 | 0x09 => integer(4),
+        0x08 => integer(8),
+        0x04 => DecodeResult {
+            result: f32::from_le_bytes(body[..4].try_into().unwrap()).to_string(),
+            offset_increment: 4,
+        },
+        0x05 => DecodeResult {
+            result: f64::from_le_bytes(body[..8].try_into().unwrap()).to_string(),
+            offset_increment: 8,
+        },
+        0x07 | 0x0a | 0x0c => {
+            let length = body[0] as usize;
+            let field = |start: usize, size: usize| match start + size <= length {
+                true => IntFixedLen::from_bytes(&body[1 + start..].to_vec(), Some(size)).result,
+                false => 0,
+            };
+
+            let mut result = format!("{:04}-{:02}-{:02}", field(0, 2), field(2, 1), field(3, 1));
+            if length > 4 {
+                result += &format!(" {:02}:{:02}:{:02}", field(4, 1), field(5, 1), field(6, 1));
+            }
+            if length > 7 {
+                result += &format!(".{:06}", field(7, 4));
+            }
+
+            DecodeResult {
+                result,
+                offset_increment: 1 + length,
+            }
+        }
+        0x0b => {
+            let length = body[0] as usize;
+            let field = |start: usize, size: usize| match start + size <= length {
+                true => IntFixedLen::from_bytes(&body[1 + start..].to_vec(), Some(size)).result,
+                false => 0,
+            };
+
+            let mut result = format!(
+                "{}{:02}:{:02}:{:02}",
+                if field(0, 1) == 1 { "-" } else { "" },
+                field(1, 4) * 24 + field(5, 1),
+                field(6, 1),
+                field(7, 1)
+            );
+            if length > 8 {
+                result += &format!(".{:06}", field(8, 4));
+            }
+
+            DecodeResult {
+                result,
+                offset_increment: 1 + length,
+            }
+        }
+        _ => StringLenEnc::from_bytes(&body.to_vec(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Connection;
+    use crate::mysql::accumulator::command::CommandAccumulator;
+    use crate::mysql::accumulator::handshake_response::HandshakeResponseAccumulator;
+    use crate::mysql::accumulator::{Accumulator, CapabilityFlags};
+    use crate::mysql::packet::{Packet, PacketHeader, PacketType};
+
+    #[test]
+    fn test_query_attributes() {
+        let mut handshake_response = HandshakeResponseAccumulator::default();
+        handshake_response.client_flag = CapabilityFlags::ClientQueryAttributes as u32;
+
+        let mut connection = Connection::default();
+        connection.handshake_response = Some(handshake_response);
+
+        let mut body = vec![0x03, 0x02, 0x01, 0b10, 0x01];
+        body.extend([0xfe, 0x00, 0x02]);
+        body.extend(b"ns");
+        body.extend([0x08, 0x00, 0x03]);
+        body.extend(b"ttl");
+        body.extend([0x05]);
+        body.extend(b"run-1");
+        body.extend(b"select 1");
+
+        let mut packet = Packet {
+            header: PacketHeader {
+                size: body.len(),
+                seq: 0,
+            },
+            body,
+            p_type: PacketType::Command,
+            skip: false,
+        };
+
+        let mut accumulator = CommandAccumulator::default();
+        accumulator.consume(&mut packet, &connection);
+
+        let command = accumulator.command.unwrap();
+        assert_eq!("select 1", command.arg);
+        assert_eq!(Some(&"run-1".to_string()), command.attributes.get("ns"));
+        assert!(!command.attributes.contains_key("ttl"));
+    }
+}
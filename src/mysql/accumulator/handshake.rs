@@ -89,22 +89,20 @@ impl Accumulator for HandshakeAccumulator {
         };
 
         let capability_flags = ((capability_flags_2 as u32) << 16) + capability_flags_1 as u32;
-        let auth_plugin_data_len;
 
-        if capability_flags & CapabilityFlags::ClientPluginAuth as u32 != 0 {
-            auth_plugin_data_len = {
+        let auth_plugin_data_len =
+            if capability_flags & CapabilityFlags::ClientPluginAuth as u32 != 0 {
                 let result = IntFixedLen::from_bytes(&packet.body[offset..].to_vec(), Some(1));
                 offset += result.offset_increment;
                 result.result as u8
-            }
-        } else {
-            assert_eq!(
-                0x00,
-                IntFixedLen::from_bytes(&packet.body[offset..].to_vec(), Some(1)).result
-            );
-            offset += 1;
-            auth_plugin_data_len = 0;
-        }
+            } else {
+                assert_eq!(
+                    0x00,
+                    IntFixedLen::from_bytes(&packet.body[offset..].to_vec(), Some(1)).result
+                );
+                offset += 1;
+                0
+            };
 
         let _reserved_string: String = {
             let result = StringFixedLen::from_bytes(&packet.body[offset..].to_vec(), Some(10));
@@ -163,6 +161,13 @@ impl Accumulator for HandshakeAccumulator {
     }
 }
 
+impl HandshakeAccumulator {
+    /// Capabilities advertised by the server.
+    pub fn capability_flags(&self) -> u32 {
+        self.capability_flags
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::connection::{Connection, Phase};
@@ -21,7 +21,7 @@ pub struct HandshakeResponseAccumulator {
     auth_response: Option<String>,
     database: Option<String>,
     client_plugin_name: Option<String>,
-    connection_attrs_length: u8,
+    connection_attrs_length: u64,
     connection_attrs: HashMap<String, String>,
     zstd_compression_level: u8,
     accumulation_complete: bool,
@@ -58,6 +58,7 @@ impl Accumulator for HandshakeResponseAccumulator {
             result.result
         };
 
+        #[allow(irrefutable_let_patterns)] // Refutable when TLS is enabled.
         if let SwitchableConnection::Plain(_) = &connection.client_connection {
             if CapabilityFlags::ClientSsl as u32 & client_flag != 0 {
                 self.accumulation_complete = true;
@@ -79,66 +80,55 @@ impl Accumulator for HandshakeResponseAccumulator {
         };
 
         let auth_response_length;
-        let auth_response;
-        if client_flag & CapabilityFlags::ClientPluginAuthLenEncClientData as u32 != 0 {
-            auth_response_length = None;
-            auth_response = {
+        let auth_response =
+            if client_flag & CapabilityFlags::ClientPluginAuthLenEncClientData as u32 != 0 {
+                auth_response_length = None;
                 let result = StringLenEnc::from_bytes(&packet.body[offset..].to_vec(), None);
                 offset += result.offset_increment;
                 Some(result.result)
-            }
-        } else {
-            auth_response_length = {
-                let result = IntFixedLen::from_bytes(&packet.body[offset..].to_vec(), Some(1));
-                offset += result.offset_increment;
-                Some(result.result as u8)
-            };
-            auth_response = {
+            } else {
+                auth_response_length = {
+                    let result = IntFixedLen::from_bytes(&packet.body[offset..].to_vec(), Some(1));
+                    offset += result.offset_increment;
+                    Some(result.result as u8)
+                };
                 let result = StringFixedLen::from_bytes(
                     &packet.body[offset..].to_vec(),
                     Some(auth_response_length.unwrap() as usize),
                 );
                 offset += result.offset_increment;
                 Some(result.result)
-            }
-        }
+            };
 
-        let database;
-        if client_flag & CapabilityFlags::ClientConnectWithDB as u32 != 0 {
-            database = {
-                let result = StringNullEnc::from_bytes(&packet.body[offset..].to_vec(), None);
-                offset += result.offset_increment;
-                Some(result.result)
-            }
+        let database = if client_flag & CapabilityFlags::ClientConnectWithDB as u32 != 0 {
+            let result = StringNullEnc::from_bytes(&packet.body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            Some(result.result)
         } else {
-            database = None;
-        }
+            None
+        };
 
-        let client_plugin_name;
-        if client_flag & CapabilityFlags::ClientPluginAuth as u32 != 0 {
-            client_plugin_name = {
-                let result = StringNullEnc::from_bytes(&packet.body[offset..].to_vec(), None);
-                offset += result.offset_increment;
-                Some(result.result)
-            }
+        let client_plugin_name = if client_flag & CapabilityFlags::ClientPluginAuth as u32 != 0 {
+            let result = StringNullEnc::from_bytes(&packet.body[offset..].to_vec(), None);
+            offset += result.offset_increment;
+            Some(result.result)
         } else {
-            client_plugin_name = None;
-        }
+            None
+        };
 
-        let connection_attrs_length;
-        if client_flag & CapabilityFlags::ClientConnectAttrs as u32 != 0 {
-            connection_attrs_length = {
+        let connection_attrs_length =
+            if client_flag & CapabilityFlags::ClientConnectAttrs as u32 != 0 {
                 let result = IntLenEnc::from_bytes(&packet.body[offset..].to_vec(), None);
                 offset += result.offset_increment;
-                result.result as u8
-            }
-        } else {
-            connection_attrs_length = 0
-        }
+                result.result
+            } else {
+                0
+            };
 
         let mut connection_attrs = HashMap::new();
-        let connection_attrs_start_offset = offset;
-        while offset < connection_attrs_start_offset + connection_attrs_length as usize {
+        let connection_attrs_end =
+            (offset + connection_attrs_length as usize).min(packet.body.len());
+        while offset < connection_attrs_end {
             let key = StringLenEnc::from_bytes(&packet.body[offset..].to_vec(), None);
             offset += key.offset_increment;
             let value = StringLenEnc::from_bytes(&packet.body[offset..].to_vec(), None);
@@ -146,16 +136,14 @@ impl Accumulator for HandshakeResponseAccumulator {
             connection_attrs.insert(key.result, value.result);
         }
 
-        let zstd_compression_level;
-        if client_flag & CapabilityFlags::ClientZSTDCompressionAlgorithm as u32 != 0 {
-            zstd_compression_level = {
+        let zstd_compression_level =
+            if client_flag & CapabilityFlags::ClientZSTDCompressionAlgorithm as u32 != 0 {
                 let result = IntLenEnc::from_bytes(&packet.body[offset..].to_vec(), Some(1));
                 // offset += result.offset_increment;
                 result.result as u8
-            }
-        } else {
-            zstd_compression_level = 0;
-        }
+            } else {
+                0
+            };
 
         self.accumulation_complete = true;
 
@@ -191,12 +179,28 @@ impl Accumulator for HandshakeResponseAccumulator {
     }
 }
 
+impl HandshakeResponseAccumulator {
+    /// Initial database requested by the client with CLIENT_CONNECT_WITH_DB.
+    pub fn database(&self) -> Option<&str> {
+        self.database.as_deref()
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn connection_attribute(&self, name: &str) -> Option<&str> {
+        self.connection_attrs.get(name).map(String::as_str)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::connection::{Connection, Phase};
     use crate::mysql::accumulator::handshake_response::HandshakeResponseAccumulator;
-    use crate::mysql::accumulator::Accumulator;
+    use crate::mysql::accumulator::{Accumulator, CapabilityFlags};
     use crate::mysql::packet::Packet;
+    use crate::mysql::types::{Converter, IntFixedLen, IntLenEnc};
 
     #[test]
     fn test_handshake_response() {
@@ -289,4 +293,46 @@ mod tests {
             HandshakeResponseAccumulator::default().consume(&mut packet.unwrap(), &connection);
         println!("{:#?}", response);
     }
+
+    #[test]
+    fn test_long_connection_attributes() {
+        let connection = Connection::default();
+
+        let mut attrs = vec![];
+        for (key, value) in [
+            ("program_name", "x".repeat(300)),
+            ("mocksysql_namespace", "tenant_a".to_string()),
+        ] {
+            for string in [key.as_bytes(), value.as_bytes()] {
+                attrs.extend(IntLenEnc::encode(string.len() as u64, None));
+                attrs.extend_from_slice(string);
+            }
+        }
+
+        let client_flag =
+            CapabilityFlags::ClientProtocol41 as u64 | CapabilityFlags::ClientConnectAttrs as u64;
+        let mut body = IntFixedLen::encode(client_flag, Some(4));
+        body.extend(IntFixedLen::encode(0xffffff, Some(4)));
+        body.push(0xff);
+        body.extend([0; 23]);
+        body.extend(b"root\0");
+        body.push(0);
+        body.extend(IntLenEnc::encode(attrs.len() as u64, None));
+        body.extend(attrs);
+
+        let mut bytes = IntFixedLen::encode(body.len() as u64, Some(3));
+        bytes.push(1);
+        bytes.extend(body);
+
+        let mut accumulator = HandshakeResponseAccumulator::default();
+        accumulator.consume(
+            &mut Packet::from_bytes(&bytes, Phase::HandshakeResponse).unwrap(),
+            &connection,
+        );
+
+        assert_eq!(
+            Some("tenant_a"),
+            accumulator.connection_attribute("mocksysql_namespace")
+        );
+    }
 }
@@ -14,6 +14,7 @@ pub mod result_set;
 pub enum CapabilityFlags {
     #[allow(dead_code)]
     ClientLongPassword = 0x01,
+    ClientFoundRows = 0x02,
     ClientConnectWithDB = 0x08,
     ClientSsl = 2048,
     ClientTransactions = 8192,
@@ -45,6 +46,11 @@ pub trait Accumulator {
 
     fn accumulation_complete(&self) -> bool;
 
+    /// Packets generated by the accumulator that need to be sent ahead of the consumed packet.
+    fn take_injected_packets(&mut self) -> Vec<Packet> {
+        vec![]
+    }
+
     fn get_accumulation_delta(&self) -> Option<AccumulationDelta> {
         None
     }
@@ -1,6 +1,9 @@
 use crate::connection::{Connection, Phase};
-use crate::materialization::evaluator::{Parse, ParseResult, Parser};
-use crate::materialization::StateDifference;
+use crate::materialization::collation::Collations;
+use crate::materialization::evaluator::{Parse, Parser};
+use crate::materialization::{
+    inserted_rows, key_values, local, local_table_key, visible_divergences, DivergenceIndex, Row,
+};
 use crate::mysql::accumulator::{AccumulationDelta, Accumulator, CapabilityFlags};
 use crate::mysql::command::MySqlCommand;
 use crate::mysql::packet::{
@@ -8,8 +11,9 @@ use crate::mysql::packet::{
 };
 use crate::mysql::types::{Converter, IntFixedLen, IntLenEnc, StringLenEnc};
 use log::debug;
-use sqlparser::ast::Statement;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{Expr, Statement};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Default, Clone)]
 pub struct ResponseAccumulator {
@@ -28,7 +32,15 @@ pub struct ResponseAccumulator {
     accumulation_complete: bool,
     error: Option<ErrorData>,
     skipped_packets: usize,
+    injected_rows: usize,
+    injected_packets: Vec<Packet>,
     warning_count: usize,
+    /// Primary key of the local table the result set reads, if every one of its columns is selected.
+    key_columns: Vec<String>,
+    /// Keys of the rows received from upstream, so local rows with the same key aren't sent twice.
+    upstream_keys: HashSet<Vec<Option<String>>>,
+    /// Divergences of the table the result set reads, applied to each row received from upstream.
+    divergences: DivergenceIndex,
 }
 
 impl Accumulator for ResponseAccumulator {
@@ -74,6 +86,10 @@ impl Accumulator for ResponseAccumulator {
         true
     }
 
+    fn take_injected_packets(&mut self) -> Vec<Packet> {
+        std::mem::take(&mut self.injected_packets)
+    }
+
     fn get_accumulation_delta(&self) -> Option<AccumulationDelta> {
         Some(AccumulationDelta {
             response: Some(self.clone()), // yuck
@@ -83,97 +99,158 @@ impl Accumulator for ResponseAccumulator {
 }
 
 impl ResponseAccumulator {
-    fn parse_row(&self, packet: &Packet) -> HashMap<String, Option<String>> {
-        let mut row = HashMap::new();
-
-        let mut i = 0;
-        let mut column_index = 0;
-        let bytes = packet.body.as_slice();
-
-        while i < packet.body.len() {
-            if bytes[i] == 0xfb {
-                row.insert(
-                    self.columns.get(column_index).unwrap().org_name.clone(),
-                    None,
-                );
-                i += 1;
-            } else {
-                let field = StringLenEnc::from_bytes(&bytes[i..].to_vec(), None);
-                row.insert(
-                    self.columns.get(column_index).unwrap().org_name.clone(),
-                    Some(field.result),
-                );
-                i += field.offset_increment;
-            }
-
-            column_index += 1;
-        }
+    /// Whether the response completed with an OK packet.
+    pub fn succeeded(&self) -> bool {
+        self.accumulation_complete && self.status == Some(PacketType::Ok)
+    }
 
-        row
+    fn parse_row(&self, packet: &Packet) -> Row {
+        parse_text_row(&packet.body, &self.columns)
     }
 
-    fn override_row(
-        &mut self,
-        packet: &mut Packet,
-        diff: &mut StateDifference,
-        connection: &Connection,
-    ) {
+    fn override_row(&mut self, packet: &mut Packet, connection: &Connection) {
         let mut row = self.parse_row(packet);
-        let mut new_body: Vec<u8> = Vec::new();
-        let mut override_state = None;
-
-        for state_changes in diff.iter().map(|(_, v)| v) {
-            if state_changes.0.is_none() {
-                override_state = Some(&state_changes.1)
-            } else if let Ok(ParseResult::Boolean(true)) =
-                Parse::evaluate(&row, &Box::new(state_changes.0.clone().unwrap()))
-            {
-                override_state = Some(&state_changes.1)
+        let collations = get_collations(&self.columns);
+
+        // Updating original hashmap to decide if row needs to be omitted in select queries based on new state.
+        if !self.divergences.apply(&mut row, 0) {
+            self.skipped_packets += 1;
+            packet.skip = true;
+        } else if let Some(selection) = get_selection(connection) {
+            // Rows the condition can't be evaluated on are kept, upstream having selected them already.
+            if let Ok(false) = Parse::evaluate(&row, &collations, &selection).map(|r| r.is_true()) {
+                self.skipped_packets += 1;
+                packet.skip = true;
             }
         }
 
-        for i in 0..row.len() {
-            let column_name = &self.columns.get(i).unwrap().org_name;
-            let mut value = row.get(column_name).unwrap();
+        let new_body = self.encode_row(&row);
 
-            if let Some(override_state) = override_state {
-                if let Some(new_value) = override_state.get(column_name) {
-                    value = new_value;
-                    // Updating original hashmap to decide if row needs to be omitted in select queries based on new state.
-                    row.insert(column_name.clone(), value.clone());
+        packet.header = PacketHeader {
+            size: new_body.len(),
+            seq: packet.header.seq, // Will be decremented by caller based on `self.skipped_packets`
+        };
+        packet.body = new_body;
+    }
+
+    /// Builds row packets for rows which only exist in the proxy (seeded, or inserted while writes are intercepted),
+    /// provided every column of the result set maps directly onto a column of the same table.
+    fn get_local_rows(&self, connection: &Connection) -> Vec<Packet> {
+        let table_name = match self.columns.first() {
+            Some(column) if !column.org_table.is_empty() => column.table_key(),
+            _ => return vec![],
+        };
+
+        if !is_single_table_select(connection)
+            || self
+                .columns
+                .iter()
+                .any(|c| c.table_key() != table_name || c.org_name.is_empty())
+        {
+            return vec![];
+        }
+
+        // (row, generated id, sequence number of the insert)
+        let mut rows: Vec<(Row, Option<u64>, u64)> = vec![];
+
+        if let Some(table) = connection
+            .tables
+            .get(&local_table_key(&connection.tables, &table_name))
+        {
+            rows.extend(table.rows.iter().map(|row| (row.clone(), None, 0)));
+        }
+
+        let collations = get_collations(&self.columns);
+        let divergences = DivergenceIndex::new(
+            visible_divergences(connection, &table_name),
+            collations.clone(),
+        );
+        rows.extend(inserted_rows(divergences.divergences()));
+
+        let selection = get_selection(connection);
+        let mut packets = vec![];
+
+        for (mut row, generated_id, since) in rows {
+            for column in &self.columns {
+                let value = row.entry(column.org_name.clone()).or_default();
+
+                if value.is_none() && column.flags & ColumnFlags::AutoIncrement as u16 != 0 {
+                    *value = generated_id.map(|id| id.to_string());
                 }
             }
 
-            new_body.extend(match value {
-                None => vec![0xfbu8],
-                Some(value) => StringLenEnc::encode(value.clone(), None),
-            })
-        }
+            if !divergences.apply(&mut row, since) {
+                continue;
+            }
 
-        if let Some(statements) = &connection.last_command.as_ref().unwrap().ast {
-            if let Some(Statement::Query(query_box)) = statements.last() {
-                let query = query_box.body.as_select();
-
-                if let Some(query) = query {
-                    if query.selection.is_some() {
-                        if let Ok(ParseResult::Boolean(b)) =
-                            Parse::evaluate(&row, &Box::new(query.clone().selection.unwrap()))
-                        {
-                            if !b {
-                                self.skipped_packets += 1;
-                                packet.skip = true;
-                            }
-                        }
-                    }
+            if let Some(selection) = &selection {
+                if !Parse::evaluate(&row, &collations, selection)
+                    .is_ok_and(|result| result.is_true())
+                {
+                    continue;
                 }
             }
+
+            // Rows with the key of one received from upstream have already been sent to the client.
+            if !self.key_columns.is_empty()
+                && self
+                    .upstream_keys
+                    .contains(&key_values(&row, &self.key_columns))
+            {
+                continue;
+            }
+
+            let body = self.encode_row(&row);
+
+            packets.push(Packet {
+                header: PacketHeader {
+                    size: body.len(),
+                    seq: 0, // Assigned by caller
+                },
+                body,
+                p_type: PacketType::Other,
+                skip: false,
+            });
         }
 
-        packet.header = PacketHeader {
-            size: new_body.len(),
-            seq: packet.header.seq, // Will be decremented by caller based on `self.skipped_packets`
+        packets
+    }
+
+    /// Primary key of the local table read by the result set, or nothing if some of its columns aren't selected.
+    fn get_key_columns(&self, connection: &Connection) -> Vec<String> {
+        let table_name = match self.columns.first() {
+            Some(column) if !column.org_table.is_empty() => column.table_key(),
+            _ => return vec![],
         };
-        packet.body = new_body;
+
+        match connection
+            .tables
+            .get(&local_table_key(&connection.tables, &table_name))
+        {
+            Some(table)
+                if table.primary_key.iter().all(|key| {
+                    self.columns
+                        .iter()
+                        .any(|c| c.table_key() == table_name && &c.org_name == key)
+                }) =>
+            {
+                table.primary_key.clone()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn encode_row(&self, row: &Row) -> Vec<u8> {
+        let mut body: Vec<u8> = Vec::new();
+
+        for column in &self.columns {
+            body.extend(match row.get(&column.org_name).cloned().flatten() {
+                None => vec![0xfbu8],
+                Some(value) => StringLenEnc::encode(value, None),
+            })
+        }
+
+        body
     }
 
     fn process_binary_result_set(
@@ -183,7 +260,7 @@ impl ResponseAccumulator {
         current_phase: Phase,
     ) -> Phase {
         if packet.p_type == PacketType::Eof
-            || (packet.header.seq == 01 && packet.p_type == PacketType::Ok)
+            || (packet.header.seq == 1 && packet.p_type == PacketType::Ok)
         {
             return Phase::Command;
         }
@@ -331,6 +408,14 @@ impl ResponseAccumulator {
                 }
             }
             State::ColumnsHydrated => {
+                self.key_columns = self.get_key_columns(connection);
+                if let Some(column) = self.columns.first() {
+                    self.divergences = DivergenceIndex::new(
+                        visible_divergences(connection, &column.table_key()),
+                        get_collations(&self.columns),
+                    );
+                }
+
                 if !connection.get_handshake_response().unwrap().client_flag
                     & CapabilityFlags::ClientDeprecateEof as u32
                     != 0
@@ -353,18 +438,39 @@ impl ResponseAccumulator {
                         status_flags = EofData::from_packet(packet, connection).status_flags
                     }
                     PacketType::Other => {
-                        if let Some(diff) = &mut connection
-                            .diff
-                            .get_mut(&self.columns.first().unwrap().org_table)
-                        {
-                            self.override_row(packet, diff, connection);
+                        if !self.divergences.is_empty() {
+                            self.override_row(packet, connection);
+                        }
+
+                        if !self.key_columns.is_empty() && !packet.skip {
+                            let row = self.parse_row(packet);
+                            self.upstream_keys
+                                .insert(key_values(&row, &self.key_columns));
                         }
                     }
                     _ => {
                         panic!("Unexpected packet type")
                     }
                 }
-                packet.header.seq -= self.skipped_packets as u8;
+                if status_flags.is_some() {
+                    let seq = packet
+                        .header
+                        .seq
+                        .wrapping_sub(self.skipped_packets as u8)
+                        .wrapping_add(self.injected_rows as u8);
+
+                    for (i, mut row) in self.get_local_rows(connection).into_iter().enumerate() {
+                        row.header.seq = seq.wrapping_add(i as u8);
+                        self.injected_rows += 1;
+                        self.injected_packets.push(row);
+                    }
+                }
+
+                packet.header.seq = packet
+                    .header
+                    .seq
+                    .wrapping_sub(self.skipped_packets as u8)
+                    .wrapping_add(self.injected_rows as u8);
 
                 if packet.p_type == PacketType::Error || status_flags.is_some() {
                     // No further data in this result set
@@ -393,6 +499,70 @@ impl ResponseAccumulator {
     }
 }
 
+/// Decodes a row of a text result set into a map keyed by the original column names.
+pub fn parse_text_row(body: &[u8], columns: &[ColumnDefinition]) -> Row {
+    let mut row = HashMap::new();
+
+    let mut i = 0;
+    let mut column_index = 0;
+
+    while i < body.len() {
+        if body[i] == 0xfb {
+            row.insert(columns.get(column_index).unwrap().org_name.clone(), None);
+            i += 1;
+        } else {
+            let field = StringLenEnc::from_bytes(&body[i..].to_vec(), None);
+            row.insert(
+                columns.get(column_index).unwrap().org_name.clone(),
+                Some(field.result),
+            );
+            i += field.offset_increment;
+        }
+
+        column_index += 1;
+    }
+
+    row
+}
+
+/// Collation ids of the result set columns, keyed the same way as [`parse_text_row`].
+pub fn get_collations(columns: &[ColumnDefinition]) -> Collations {
+    columns
+        .iter()
+        .map(|column| (column.org_name.clone(), column.character_set))
+        .collect()
+}
+
+fn get_selection(connection: &Connection) -> Option<Expr> {
+    if let Some(statements) = &connection.last_command.as_ref().unwrap().ast {
+        if let Some(Statement::Query(query_box)) = statements.last() {
+            if let Some(query) = query_box.body.as_select() {
+                return query.selection.clone();
+            }
+        }
+    }
+
+    None
+}
+
+/// Whether local rows can be appended to the result of the last command. Rows can't be added to a grouped, ordered or
+/// limited result without redoing the work of the server, so these are left as they are.
+fn is_single_table_select(connection: &Connection) -> bool {
+    let last_command = connection.last_command.as_ref().unwrap();
+
+    if last_command.com_code != MySqlCommand::ComQuery {
+        return false;
+    }
+
+    if let Some(statements) = &last_command.ast {
+        if let Some(Statement::Query(query)) = statements.last() {
+            return local::is_plain_select(query) && query.limit_clause.is_none();
+        }
+    }
+
+    false
+}
+
 #[derive(Debug, Default, Clone)]
 enum State {
     #[default]
@@ -407,26 +577,31 @@ enum State {
     Complete,
 }
 
+#[repr(u16)]
+enum ColumnFlags {
+    AutoIncrement = 0x200,
+}
+
 #[derive(Debug, Default, Clone)]
 #[allow(dead_code)]
-struct ColumnDefinition {
-    catalog: String,
-    schema: String,
-    table: String,
-    org_table: String,
-    name: String,
-    org_name: String,
-    fixed_length_fields: u64,
-    character_set: u16,
-    column_length: u32,
-    field_type: FieldTypes,
-    flags: u16,
-    decimals: u8,
-    reserved: u16,
+pub struct ColumnDefinition {
+    pub catalog: String,
+    pub schema: String,
+    pub table: String,
+    pub org_table: String,
+    pub name: String,
+    pub org_name: String,
+    pub fixed_length_fields: u64,
+    pub character_set: u16,
+    pub column_length: u32,
+    pub field_type: FieldTypes,
+    pub flags: u16,
+    pub decimals: u8,
+    pub reserved: u16,
 }
 
 impl ColumnDefinition {
-    fn from_packet(packet: &Packet) -> ColumnDefinition {
+    pub fn from_packet(packet: &Packet) -> ColumnDefinition {
         let body = &packet.body;
         let mut offset = 0;
 
@@ -502,12 +677,53 @@ impl ColumnDefinition {
             },
         }
     }
+
+    pub fn to_packet(&self, sequence: u8) -> Packet {
+        let mut body: Vec<u8> = Vec::new();
+
+        for value in [
+            &self.catalog,
+            &self.schema,
+            &self.table,
+            &self.org_table,
+            &self.name,
+            &self.org_name,
+        ] {
+            body.extend(StringLenEnc::encode(value.clone(), None));
+        }
+        body.extend(IntLenEnc::encode(self.fixed_length_fields, None));
+        body.extend(IntFixedLen::encode(self.character_set as u64, Some(2)));
+        body.extend(IntFixedLen::encode(self.column_length as u64, Some(4)));
+        body.push(self.field_type.clone() as u8);
+        body.extend(IntFixedLen::encode(self.flags as u64, Some(2)));
+        body.push(self.decimals);
+        body.extend(IntFixedLen::encode(self.reserved as u64, Some(2)));
+
+        Packet {
+            header: PacketHeader {
+                size: body.len(),
+                seq: sequence,
+            },
+            body,
+            p_type: PacketType::Other,
+            skip: false,
+        }
+    }
+
+    /// Key of the table the column originates from, matching the keys of the diff log and table store.
+    pub fn table_key(&self) -> String {
+        match self.schema.is_empty() {
+            true => self.org_table.clone(),
+            false => format!("{}.{}", self.schema, self.org_table),
+        }
+    }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[allow(dead_code)]
 #[repr(u8)]
-enum FieldTypes {
+pub enum FieldTypes {
     MysqlTypeDecimal,
     MysqlTypeTiny,
     MysqlTypeShort,
@@ -594,7 +810,10 @@ impl TryFrom<u16> for FieldTypes {
 #[cfg(test)]
 mod tests {
     use crate::connection::Phase;
+    use crate::materialization;
+    use crate::materialization::LocalTable;
     use crate::mysql::accumulator::result_set::*;
+    use crate::mysql::command::Command;
 
     #[test]
     fn test_column_definition_decode() {
@@ -615,4 +834,177 @@ mod tests {
         let c_def = ColumnDefinition::from_packet(&packet);
         println!("{:?}", c_def);
     }
+
+    #[test]
+    fn test_local_rows_appended_to_select() {
+        let mut connection = Connection::default();
+        connection.current_database = Some("test".to_string());
+        connection.tables.insert(
+            "account".to_string(),
+            LocalTable {
+                auto_increment: Some("id".to_string()),
+                next_auto_increment: Some(101),
+                ..LocalTable::default()
+            },
+        );
+
+        materialization::get_diff(
+            &mut connection.diff,
+            &connection.tables,
+            connection.current_database.as_deref(),
+            &Command::from_bytes(
+                MySqlCommand::ComQuery,
+                b"insert into account (status) values ('ACTIVE'), ('CLOSED')",
+            )
+            .ast,
+        );
+
+        connection.last_command = Some(Command::from_bytes(
+            MySqlCommand::ComQuery,
+            b"select id, status from account where status = 'CLOSED'",
+        ));
+
+        let column = |name: &str, flags: u16| ColumnDefinition {
+            schema: "test".to_string(),
+            org_table: "account".to_string(),
+            org_name: name.to_string(),
+            flags,
+            ..ColumnDefinition::default()
+        };
+
+        let accumulator = ResponseAccumulator {
+            columns: vec![
+                column("id", ColumnFlags::AutoIncrement as u16),
+                column("status", 0),
+            ],
+            ..ResponseAccumulator::default()
+        };
+
+        let rows = accumulator.get_local_rows(&connection);
+
+        assert_eq!(1, rows.len());
+        assert_eq!(b"\x03102\x06CLOSED".to_vec(), rows[0].body);
+    }
+
+    #[test]
+    fn test_local_rows_not_appended_to_limited_or_grouped_select() {
+        let mut connection = Connection::default();
+        connection.tables.insert(
+            "account".to_string(),
+            LocalTable {
+                rows: vec![
+                    HashMap::from([("status".to_string(), Some("ACTIVE".to_string()))]),
+                    HashMap::from([("status".to_string(), Some("ACTIVE".to_string()))]),
+                ],
+                ..LocalTable::default()
+            },
+        );
+
+        let accumulator = ResponseAccumulator {
+            columns: vec![ColumnDefinition {
+                org_table: "account".to_string(),
+                org_name: "status".to_string(),
+                ..ColumnDefinition::default()
+            }],
+            ..ResponseAccumulator::default()
+        };
+
+        for query in [
+            "select status from account where status = 'ACTIVE' limit 1",
+            "select status from account group by status",
+            "select distinct status from account",
+            "select status from account order by status",
+        ] {
+            connection.last_command = Some(Command::from_bytes(
+                MySqlCommand::ComQuery,
+                query.as_bytes(),
+            ));
+            assert!(
+                accumulator.get_local_rows(&connection).is_empty(),
+                "{}",
+                query
+            );
+        }
+
+        connection.last_command = Some(Command::from_bytes(
+            MySqlCommand::ComQuery,
+            b"select status from account",
+        ));
+        assert_eq!(2, accumulator.get_local_rows(&connection).len());
+    }
+
+    #[test]
+    fn test_local_rows_deduplicated_by_key() {
+        let mut connection = Connection::default();
+        connection.tables.insert(
+            "account".to_string(),
+            LocalTable {
+                primary_key: vec!["id".to_string()],
+                rows: vec![
+                    HashMap::from([("id".to_string(), Some("1".to_string()))]),
+                    HashMap::from([("id".to_string(), Some("2".to_string()))]),
+                ],
+                ..LocalTable::default()
+            },
+        );
+        connection.last_command = Some(Command::from_bytes(
+            MySqlCommand::ComQuery,
+            b"select id from account",
+        ));
+
+        let mut accumulator = ResponseAccumulator {
+            columns: vec![ColumnDefinition {
+                org_table: "account".to_string(),
+                org_name: "id".to_string(),
+                ..ColumnDefinition::default()
+            }],
+            ..ResponseAccumulator::default()
+        };
+        accumulator.key_columns = accumulator.get_key_columns(&connection);
+        accumulator
+            .upstream_keys
+            .insert(vec![Some("1".to_string())]);
+
+        let rows = accumulator.get_local_rows(&connection);
+
+        assert_eq!(1, rows.len());
+        assert_eq!(b"\x012".to_vec(), rows[0].body);
+    }
+
+    #[test]
+    fn test_deleted_rows_hidden() {
+        let mut connection = Connection::default();
+
+        for query in [
+            "insert into account (id, status) values (1, 'ACTIVE'), (2, 'CLOSED')",
+            "delete from account where status = 'CLOSED'",
+        ] {
+            materialization::get_diff(
+                &mut connection.diff,
+                &connection.tables,
+                Some("test"),
+                &Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes()).ast,
+            );
+        }
+
+        connection.last_command = Some(Command::from_bytes(
+            MySqlCommand::ComQuery,
+            b"select id from account",
+        ));
+
+        let accumulator = ResponseAccumulator {
+            columns: vec![ColumnDefinition {
+                schema: "test".to_string(),
+                org_table: "account".to_string(),
+                org_name: "id".to_string(),
+                ..ColumnDefinition::default()
+            }],
+            ..ResponseAccumulator::default()
+        };
+
+        let rows = accumulator.get_local_rows(&connection);
+
+        assert_eq!(1, rows.len());
+        assert_eq!(b"\x011".to_vec(), rows[0].body);
+    }
 }
@@ -1,6 +1,8 @@
-use sqlparser::ast::Statement;
+use crate::materialization::unqualified_name;
+use sqlparser::ast::{Statement, Use};
 use sqlparser::dialect::MySqlDialect;
 use sqlparser::parser::Parser;
+use std::collections::HashMap;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
@@ -8,6 +10,8 @@ pub struct Command {
     pub com_code: MySqlCommand,
     pub arg: String,
     pub ast: Option<Vec<Statement>>,
+    /// Query attributes sent along with COM_QUERY, keyed by name.
+    pub attributes: HashMap<String, String>,
 }
 
 impl Command {
@@ -24,6 +28,21 @@ impl Command {
             com_code,
             arg,
             ast: parsed.ok(),
+            attributes: HashMap::new(),
+        }
+    }
+
+    /// Database selected by the command, i.e, the argument of COM_INIT_DB or the target of a `USE` statement.
+    pub fn selected_database(&self) -> Option<String> {
+        match self.com_code {
+            MySqlCommand::ComInitDb => Some(self.arg.clone()),
+            MySqlCommand::ComQuery => match self.ast.as_ref()?.last()? {
+                Statement::Use(Use::Object(name) | Use::Database(name) | Use::Schema(name)) => {
+                    Some(unqualified_name(name))
+                }
+                _ => None,
+            },
+            _ => None,
         }
     }
 }
@@ -106,3 +125,20 @@ impl MySqlCommand {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::mysql::command::{Command, MySqlCommand};
+
+    #[test]
+    fn test_selected_database() {
+        let use_statement = Command::from_bytes(MySqlCommand::ComQuery, b"USE `shop`");
+        assert_eq!(Some("shop".to_string()), use_statement.selected_database());
+
+        let init_db = Command::from_bytes(MySqlCommand::ComInitDb, b"crm");
+        assert_eq!(Some("crm".to_string()), init_db.selected_database());
+
+        let select = Command::from_bytes(MySqlCommand::ComQuery, b"select database()");
+        assert_eq!(None, select.selected_database());
+    }
+}
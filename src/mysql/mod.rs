@@ -1,4 +1,5 @@
 pub mod accumulator;
 pub mod command;
 pub mod packet;
+pub mod text_result_set;
 pub mod types;
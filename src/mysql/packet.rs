@@ -1,9 +1,11 @@
 use crate::connection::{Connection, Phase};
 use crate::mysql::accumulator::CapabilityFlags;
-use crate::mysql::types::{Converter, IntFixedLen, IntLenEnc, StringEOFEnc, StringFixedLen};
-use std::{fmt::Error, usize};
+use crate::mysql::types::{
+    Converter, IntFixedLen, IntLenEnc, StringEOFEnc, StringFixedLen, StringLenEnc,
+};
+use std::fmt::Error;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Packet {
     pub header: PacketHeader,
     pub body: Vec<u8>,
@@ -20,7 +22,7 @@ pub enum PacketType {
     Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PacketHeader {
     pub size: usize,
     pub seq: u8,
@@ -70,7 +72,7 @@ impl Packet {
         })
     }
 
-    pub fn to_bytes(self) -> Vec<u8> {
+    pub fn to_bytes(&self) -> Vec<u8> {
         let mut ret: Vec<u8> = Vec::new();
 
         ret.extend(self.header.to_bytes());
@@ -117,6 +119,42 @@ pub struct ErrorData {
 }
 
 impl ErrorData {
+    pub fn new(error_code: u16, state: &str, error_message: String) -> ErrorData {
+        ErrorData {
+            error_code,
+            sql_state: Some(SQLState {
+                state_marker: "#".to_string(),
+                state: state.to_string(),
+            }),
+            error_message,
+        }
+    }
+
+    pub fn to_packet(&self, sequence: u8, client_flag: u32) -> Packet {
+        let mut body: Vec<u8> = vec![0xff];
+
+        body.extend(IntFixedLen::encode(self.error_code as u64, Some(2)));
+
+        if client_flag & CapabilityFlags::ClientProtocol41 as u32 != 0 {
+            if let Some(sql_state) = &self.sql_state {
+                body.extend(sql_state.state_marker.as_bytes());
+                body.extend(sql_state.state.as_bytes());
+            }
+        }
+
+        body.extend(self.error_message.as_bytes());
+
+        Packet {
+            header: PacketHeader {
+                size: body.len(),
+                seq: sequence,
+            },
+            body,
+            p_type: PacketType::Error,
+            skip: false,
+        }
+    }
+
     pub fn from_packet(packet: &Packet, connection: &Connection) -> ErrorData {
         assert_eq!(packet.p_type, PacketType::Error);
         let body = &packet.body;
@@ -185,7 +223,10 @@ pub struct SessionState {
 }
 
 #[repr(u16)]
+#[allow(clippy::enum_variant_names)]
 pub enum ServerStatusFlags {
+    ServerStatusInTrans = 0x01,
+    ServerStatusAutocommit = 0x02,
     ServerMoreResultsExist = 0x08,
     #[allow(dead_code)]
     ServerSessionStateChanged = 0x01 << 14,
@@ -300,7 +341,12 @@ impl OkData {
         }
 
         if client_flag & CapabilityFlags::ClientSessionTrack as u32 != 0 {
+            if let Some(info) = &self.info {
+                body.extend(StringLenEnc::encode(info.clone(), None));
+            }
             // TODO: Add session track data
+        } else if let Some(info) = &self.info {
+            body.extend(info.as_bytes());
         }
 
         Packet {
@@ -319,10 +365,42 @@ impl OkData {
 #[allow(dead_code)]
 pub struct EofData {
     pub status_flags: Option<u16>,
-    warnings: Option<u16>,
+    pub warnings: Option<u16>,
 }
 
 impl EofData {
+    pub fn new(status_flags: u16) -> EofData {
+        EofData {
+            status_flags: Some(status_flags),
+            warnings: Some(0),
+        }
+    }
+
+    pub fn to_packet(&self, sequence: u8, client_flag: u32) -> Packet {
+        let mut body: Vec<u8> = vec![0xfe];
+
+        if client_flag & CapabilityFlags::ClientProtocol41 as u32 != 0 {
+            body.extend(IntFixedLen::encode(
+                self.warnings.unwrap_or(0) as u64,
+                Some(2),
+            ));
+            body.extend(IntFixedLen::encode(
+                self.status_flags.unwrap_or(0) as u64,
+                Some(2),
+            ));
+        }
+
+        Packet {
+            header: PacketHeader {
+                size: body.len(),
+                seq: sequence,
+            },
+            body,
+            p_type: PacketType::Eof,
+            skip: false,
+        }
+    }
+
     pub fn from_packet(packet: &Packet, connection: &Connection) -> EofData {
         assert_eq!(packet.p_type, PacketType::Eof);
 
@@ -0,0 +1,147 @@
+use crate::mysql::accumulator::result_set::{ColumnDefinition, FieldTypes};
+use crate::mysql::accumulator::CapabilityFlags;
+use crate::mysql::packet::{EofData, OkData, Packet, PacketHeader, PacketType, ServerStatusFlags};
+use crate::mysql::types::{Converter, IntLenEnc, StringLenEnc};
+
+/// utf8mb4_0900_ai_ci
+const UTF8MB4_COLLATION: u16 = 255;
+
+/// Result set synthesized by the proxy, encoded with the text protocol as the response to a COM_QUERY.
+/// Every column is sent as a VARCHAR.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TextResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+impl TextResultSet {
+    pub fn new(columns: &[&str]) -> TextResultSet {
+        TextResultSet {
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn push_row(&mut self, row: Vec<Option<String>>) {
+        assert_eq!(self.columns.len(), row.len());
+        self.rows.push(row);
+    }
+
+    /// Encodes the column count, column definitions, rows and the terminating EOF/OK packet, numbered from
+    /// `sequence` onwards.
+    pub fn to_packets(&self, sequence: u8, client_flag: u32) -> Vec<Packet> {
+        let deprecate_eof = client_flag & CapabilityFlags::ClientDeprecateEof as u32 != 0;
+        let mut sequence = sequence;
+        let mut next_sequence = || {
+            let current = sequence;
+            sequence = sequence.wrapping_add(1);
+            current
+        };
+
+        let mut packets = vec![];
+
+        let mut column_count: Vec<u8> = Vec::new();
+        if client_flag & CapabilityFlags::ClientOptionalResultSetMetadata as u32 != 0 {
+            // RESULTSET_METADATA_FULL
+            column_count.push(0x01);
+        }
+        column_count.extend(IntLenEnc::encode(self.columns.len() as u64, None));
+        packets.push(packet(next_sequence(), column_count, PacketType::Other));
+
+        for column in &self.columns {
+            packets.push(column_definition(column).to_packet(next_sequence()));
+        }
+
+        let status_flags = ServerStatusFlags::ServerStatusAutocommit as u16;
+
+        if !deprecate_eof {
+            packets.push(EofData::new(status_flags).to_packet(next_sequence(), client_flag));
+        }
+
+        for row in &self.rows {
+            let mut body: Vec<u8> = Vec::new();
+            for value in row {
+                body.extend(match value {
+                    None => vec![0xfbu8],
+                    Some(value) => StringLenEnc::encode(value.clone(), None),
+                })
+            }
+            packets.push(packet(next_sequence(), body, PacketType::Other));
+        }
+
+        packets.push(match deprecate_eof {
+            true => OkData {
+                header: 0xfe,
+                affected_rows: 0,
+                last_insert_id: 0,
+                status_flags: Some(status_flags),
+                warnings: Some(0),
+                info: None,
+                session_state_info: None,
+            }
+            .to_packet(next_sequence(), client_flag),
+            false => EofData::new(status_flags).to_packet(next_sequence(), client_flag),
+        });
+
+        packets
+    }
+}
+
+fn column_definition(name: &str) -> ColumnDefinition {
+    ColumnDefinition {
+        catalog: "def".to_string(),
+        name: name.to_string(),
+        org_name: name.to_string(),
+        fixed_length_fields: 0x0c,
+        character_set: UTF8MB4_COLLATION,
+        column_length: 1024,
+        field_type: FieldTypes::MysqlTypeVarString,
+        ..ColumnDefinition::default()
+    }
+}
+
+fn packet(sequence: u8, body: Vec<u8>, p_type: PacketType) -> Packet {
+    Packet {
+        header: PacketHeader {
+            size: body.len(),
+            seq: sequence,
+        },
+        body,
+        p_type,
+        skip: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mysql::accumulator::result_set::ColumnDefinition;
+    use crate::mysql::accumulator::CapabilityFlags;
+    use crate::mysql::packet::PacketType;
+    use crate::mysql::text_result_set::TextResultSet;
+
+    #[test]
+    fn test_text_result_set_packets() {
+        let mut result_set = TextResultSet::new(&["table", "statement"]);
+        result_set.push_row(vec![Some("shop.account".to_string()), None]);
+
+        let client_flag = CapabilityFlags::ClientProtocol41 as u32;
+        let packets = result_set.to_packets(1, client_flag);
+
+        assert_eq!(6, packets.len());
+        assert_eq!(
+            vec![1, 2, 3, 4, 5, 6],
+            packets.iter().map(|p| p.header.seq).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![0x02], packets[0].body);
+        assert_eq!("statement", ColumnDefinition::from_packet(&packets[2]).name);
+        assert_eq!(PacketType::Eof, packets[3].p_type);
+        assert_eq!(b"\x0cshop.account\xfb".to_vec(), packets[4].body);
+        assert_eq!(vec![0xfe, 0x00, 0x00, 0x02, 0x00], packets[5].body);
+
+        let packets =
+            result_set.to_packets(1, client_flag | CapabilityFlags::ClientDeprecateEof as u32);
+        assert_eq!(5, packets.len());
+        assert_eq!(PacketType::Ok, packets[4].p_type);
+        assert_eq!(0xfe, packets[4].body[0]);
+    }
+}
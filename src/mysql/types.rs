@@ -6,6 +6,7 @@ pub struct DecodeResult<T> {
     pub offset_increment: usize,
 }
 
+#[allow(clippy::ptr_arg)]
 pub trait Converter<T> {
     fn from_bytes(bytes: &Vec<u8>, length: Option<usize>) -> DecodeResult<T>;
 
@@ -120,7 +121,7 @@ impl Converter<u64> for IntLenEnc {
         } else {
             // 8-byte encoding (0xFE followed by 8 bytes)
             let mut bytes = vec![0xFE];
-            bytes.extend_from_slice(&(value as u64).to_le_bytes());
+            bytes.extend_from_slice(&value.to_le_bytes());
             bytes
         }
     }
@@ -154,8 +155,8 @@ impl Converter<String> for StringNullEnc {
     fn from_bytes(bytes: &Vec<u8>, _length: Option<usize>) -> DecodeResult<String> {
         let mut null_position: Option<usize> = None;
 
-        for i in 0..bytes.len() {
-            if bytes[i] == 0x00 {
+        for (i, byte) in bytes.iter().enumerate() {
+            if *byte == 0x00 {
                 null_position = Some(i);
                 break;
             }
@@ -0,0 +1,92 @@
+use crate::replay::memory::MemoryStore;
+use crate::replay::{ReplayLogEntry, ReplayStore};
+use log::error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Mutex;
+
+/// Appends responses to a JSONL file, one [`ReplayLogEntry`] per line, so recordings can be replayed offline.
+/// The file is loaded at startup, entries for the same command being replayed in the order they were recorded.
+pub struct FileStore {
+    file: Mutex<File>,
+    responses: MemoryStore,
+}
+
+impl FileStore {
+    pub fn open(path: &str) -> Result<FileStore, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+
+        let responses = MemoryStore::default();
+        for (i, line) in BufReader::new(&file).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<ReplayLogEntry>(&line) {
+                Ok(entry) => responses.record(entry)?,
+                Err(e) => error!("Skipping line {} of {}: {}", i + 1, path, e),
+            }
+        }
+
+        Ok(FileStore {
+            file: Mutex::new(file),
+            responses,
+        })
+    }
+}
+
+impl ReplayStore for FileStore {
+    fn record(&self, entry: ReplayLogEntry) -> Result<(), String> {
+        let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", line).map_err(|e| e.to_string())?;
+        file.flush().map_err(|e| e.to_string())?;
+
+        self.responses.record(entry)
+    }
+
+    fn lookup(&self, key: &str) -> Vec<ReplayLogEntry> {
+        self.responses.lookup(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::file::FileStore;
+    use crate::replay::fingerprint::replay_key;
+    use crate::replay::response::ResponsePart;
+    use crate::replay::{ReplayLogEntry, ReplayStore};
+    use std::env;
+
+    #[test]
+    fn test_file_store_reloads_recordings() {
+        let path = env::temp_dir().join(format!("mocksysql-replay-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let entry = |output: &str| ReplayLogEntry {
+            last_command: "select * from account".to_string(),
+            response: vec![ResponsePart::Packet(output.to_string())],
+        };
+
+        let store = FileStore::open(path).unwrap();
+        for output in ["AQ==", "Ag=="] {
+            store.record(entry(output)).unwrap();
+        }
+        drop(store);
+
+        let store = FileStore::open(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            vec![entry("AQ=="), entry("Ag==")],
+            store.lookup(&replay_key("SELECT * FROM account"))
+        );
+        assert!(store.lookup(&replay_key("select 1")).is_empty());
+    }
+}
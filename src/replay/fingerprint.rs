@@ -0,0 +1,169 @@
+use log::error;
+use once_cell::sync::Lazy;
+use sqlparser::dialect::MySqlDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::env;
+
+/// How closely a command has to match a recorded one for its response to be replayed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strictness {
+    /// The raw text of the commands must be identical.
+    Exact,
+    /// Commands must have the same fingerprint and the same literal values.
+    Normalized,
+    /// Commands must have the same fingerprint, whatever their literal values.
+    Fingerprint,
+}
+
+/// One of `exact`, `normalized` or `fingerprint`.
+static REPLAY_KEY_STRICTNESS: Lazy<Strictness> = Lazy::new(|| {
+    match env::var("REPLAY_KEY_STRICTNESS")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "exact" => Strictness::Exact,
+        "fingerprint" => Strictness::Fingerprint,
+        "" | "normalized" => Strictness::Normalized,
+        strictness => {
+            error!(
+                "Unknown REPLAY_KEY_STRICTNESS {}, using normalized",
+                strictness
+            );
+            Strictness::Normalized
+        }
+    }
+});
+
+/// Key a response to the command is recorded and looked up under, according to `REPLAY_KEY_STRICTNESS`.
+pub fn replay_key(sql: &str) -> String {
+    key(sql, *REPLAY_KEY_STRICTNESS)
+}
+
+fn key(sql: &str, strictness: Strictness) -> String {
+    if strictness == Strictness::Exact {
+        return sql.to_string();
+    }
+
+    let (fingerprint, parameters) = fingerprint(sql);
+    match strictness {
+        Strictness::Normalized => format!(
+            "{} {}",
+            fingerprint,
+            serde_json::to_string(&parameters).unwrap()
+        ),
+        _ => fingerprint,
+    }
+}
+
+/// Canonical form of the statement with its literals replaced by `?`, and the literals in the order they appear.
+/// The statement is formatted from its AST when it parses, so that layout and keyword case don't matter. Comments
+/// are dropped, keywords lowercased and whitespace collapsed. Identifiers keep their case, as table names may be
+/// case sensitive.
+pub fn fingerprint(sql: &str) -> (String, Vec<String>) {
+    let canonical = match Parser::parse_sql(&MySqlDialect {}, sql) {
+        Ok(statements) => statements
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; "),
+        Err(_) => sql.to_string(),
+    };
+
+    let tokens = match Tokenizer::new(&MySqlDialect {}, &canonical).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return (canonical.trim().to_string(), vec![]),
+    };
+
+    let mut fingerprint = String::new();
+    let mut parameters = vec![];
+    let mut pending_space = false;
+
+    for token in tokens {
+        let text = match token {
+            // Comments are whitespace to the tokenizer.
+            Token::Whitespace(_) => {
+                pending_space = true;
+                continue;
+            }
+            Token::Word(word)
+                if word.quote_style.is_none() && word.keyword != Keyword::NoKeyword =>
+            {
+                word.value.to_lowercase()
+            }
+            Token::Number(..)
+            | Token::SingleQuotedString(_)
+            | Token::DoubleQuotedString(_)
+            | Token::NationalStringLiteral(_)
+            | Token::EscapedStringLiteral(_)
+            | Token::HexStringLiteral(_)
+            | Token::SingleQuotedByteStringLiteral(_)
+            | Token::DoubleQuotedByteStringLiteral(_) => {
+                parameters.push(token.to_string());
+                "?".to_string()
+            }
+            token => token.to_string(),
+        };
+
+        if pending_space && !fingerprint.is_empty() {
+            fingerprint.push(' ');
+        }
+        pending_space = false;
+        fingerprint.push_str(&text);
+    }
+
+    (fingerprint, parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::fingerprint::{fingerprint, key, Strictness};
+
+    #[test]
+    fn test_fingerprint() {
+        let (fingerprint_1, parameters) = fingerprint(
+            "select *  from Customer\n-- active accounts\nwhere id=1 and status = \"A\"",
+        );
+        assert_eq!(
+            "select * from Customer where id = ? and status = ?",
+            fingerprint_1
+        );
+        assert_eq!(vec!["1", "\"A\""], parameters);
+
+        assert_eq!(
+            (
+                fingerprint_1.clone(),
+                vec!["2".to_string(), "'B'".to_string()]
+            ),
+            fingerprint("SELECT * /* all */ FROM Customer WHERE id = 2 AND STATUS = 'B';")
+        );
+        // Identifiers keep their case, statements that don't parse are still normalised.
+        assert_eq!(
+            (
+                "select `Id` from T where x = ? ,".to_string(),
+                vec!["1".to_string()]
+            ),
+            fingerprint("SELECT `Id` FROM T WHERE x = 1 ,")
+        );
+        assert_ne!(
+            fingerprint("SELECT * FROM Customer").0,
+            fingerprint("SELECT * FROM customer").0
+        );
+
+        let query = "select * from account where id = 1";
+        assert_eq!(query, key(query, Strictness::Exact));
+        assert_eq!(
+            "select * from account where id = ? [\"1\"]",
+            key(query, Strictness::Normalized)
+        );
+        assert_eq!(
+            key(
+                "SELECT * FROM account WHERE id = 2",
+                Strictness::Fingerprint
+            ),
+            key(query, Strictness::Fingerprint)
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::replay::memory::MemoryStore;
+use crate::replay::{ReplayLogEntry, ReplayStore};
+use kafka::consumer::Consumer;
+use kafka::producer::{Producer, Record, RequiredAcks};
+use log::{debug, error};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Publishes responses to a Kafka topic, and replays the responses consumed from it.
+pub struct KafkaStore {
+    topic: String,
+    producer: Option<Mutex<Producer>>,
+    responses: MemoryStore,
+}
+
+impl KafkaStore {
+    /// Connects to `KAFKA_HOST`, producing to and/or consuming from `KAFKA_TOPIC` depending on the enabled modes.
+    pub fn connect(record: bool, replay: bool) -> Arc<KafkaStore> {
+        let kafka_host = env::var("KAFKA_HOST").expect("KAFKA_HOST is not set");
+        let kafka_topic = env::var("KAFKA_TOPIC").expect("KAFKA_TOPIC is not set");
+        let hosts: Vec<String> = kafka_host.split(",").map(|s| s.to_string()).collect();
+
+        let producer = record.then(|| {
+            Mutex::new(
+                Producer::from_hosts(hosts.clone())
+                    .with_ack_timeout(Duration::from_secs(1))
+                    .with_required_acks(RequiredAcks::None)
+                    .create()
+                    .unwrap(),
+            )
+        });
+
+        let store = Arc::new(KafkaStore {
+            topic: kafka_topic.clone(),
+            producer,
+            responses: MemoryStore::default(),
+        });
+
+        if replay {
+            let consumer = Consumer::from_hosts(hosts)
+                .with_topic(kafka_topic)
+                .with_fetch_max_bytes_per_partition(16 * 1024 * 1024) // 16MB
+                .create()
+                .unwrap();
+            spawn_kafka_read(consumer, store.clone());
+        }
+
+        store
+    }
+}
+
+impl ReplayStore for KafkaStore {
+    fn record(&self, entry: ReplayLogEntry) -> Result<(), String> {
+        let producer = match &self.producer {
+            Some(producer) => producer,
+            None => return Ok(()),
+        };
+
+        let payload = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        producer
+            .lock()
+            .unwrap()
+            .send(&Record::from_value(
+                &self.topic,
+                payload.as_bytes().to_vec(),
+            ))
+            .map_err(|e| e.to_string())
+    }
+
+    fn lookup(&self, key: &str) -> Vec<ReplayLogEntry> {
+        self.responses.lookup(key)
+    }
+}
+
+fn spawn_kafka_read(mut consumer: Consumer, store: Arc<KafkaStore>) {
+    std::thread::spawn(move || loop {
+        match consumer.poll() {
+            Ok(poll) => {
+                for ms in poll.iter() {
+                    for m in ms.messages() {
+                        let message_string = String::from_utf8(m.value.to_vec()).unwrap();
+
+                        match serde_json::from_str::<ReplayLogEntry>(&message_string) {
+                            Ok(entry) => {
+                                debug!("{:?}", entry);
+                                let _ = store.responses.record(entry);
+                            }
+                            Err(e) => println!("Error deserializing replay log entry, {}", e),
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Error receiving messages, {:?}", e);
+            }
+        }
+    });
+}
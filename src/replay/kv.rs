@@ -0,0 +1,142 @@
+use crate::replay::{ReplayLogEntry, ReplayStore};
+use log::error;
+use redb::{Database, ReadableTable, TableDefinition};
+
+/// (replay key, index) -> recorded entry as JSON, entries of a key being indexed in the order they were recorded.
+/// Appending an entry leaves the others untouched, however many responses were recorded for the key.
+const ENTRIES: TableDefinition<(&str, u64), &str> = TableDefinition::new("entries");
+
+/// Keeps responses in an embedded key-value database, so large recordings needn't be loaded into memory.
+pub struct KvStore {
+    database: Database,
+}
+
+impl KvStore {
+    pub fn open(path: &str) -> Result<KvStore, String> {
+        Ok(KvStore {
+            database: Database::create(path).map_err(|e| e.to_string())?,
+        })
+    }
+}
+
+impl ReplayStore for KvStore {
+    fn record(&self, entry: ReplayLogEntry) -> Result<(), String> {
+        let transaction = self.database.begin_write().map_err(|e| e.to_string())?;
+        {
+            let mut table = transaction.open_table(ENTRIES).map_err(|e| e.to_string())?;
+            let key = entry.key();
+
+            let index = match table
+                .range((key.as_str(), 0)..=(key.as_str(), u64::MAX))
+                .map_err(|e| e.to_string())?
+                .next_back()
+            {
+                Some(last) => last.map_err(|e| e.to_string())?.0.value().1 + 1,
+                None => 0,
+            };
+
+            let entry = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+            table
+                .insert((key.as_str(), index), entry.as_str())
+                .map_err(|e| e.to_string())?;
+        }
+        transaction.commit().map_err(|e| e.to_string())
+    }
+
+    fn lookup(&self, key: &str) -> Vec<ReplayLogEntry> {
+        let mut entries: Vec<ReplayLogEntry> = vec![];
+        // Undecodable entries are skipped, the others can still be replayed.
+        let mut push = |entry: Result<ReplayLogEntry, String>| match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => error!("Skipping replay entry for {}: {}", key, e),
+        };
+
+        let transaction = match self.database.begin_read() {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                error!("Unable to read replay entries for {}: {}", key, e);
+                return vec![];
+            }
+        };
+
+        // The table only exists once something was recorded.
+        if let Ok(table) = transaction.open_table(ENTRIES) {
+            match table.range((key, 0)..=(key, u64::MAX)) {
+                Ok(range) => range.for_each(|entry| {
+                    push(entry.map_err(|e| e.to_string()).and_then(|(_, entry)| {
+                        serde_json::from_str(entry.value()).map_err(|e| e.to_string())
+                    }))
+                }),
+                Err(e) => push(Err(e.to_string())),
+            }
+        }
+
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::fingerprint::replay_key;
+    use crate::replay::kv::{KvStore, ENTRIES};
+    use crate::replay::response::ResponsePart;
+    use crate::replay::{ReplayLogEntry, ReplayStore};
+    use std::env;
+
+    #[test]
+    fn test_kv_store() {
+        let path = env::temp_dir().join(format!("mocksysql-replay-{}.redb", std::process::id()));
+        let path = path.to_str().unwrap();
+        let entry = |output: &str| ReplayLogEntry {
+            last_command: "select 1".to_string(),
+            response: vec![ResponsePart::Packet(output.to_string())],
+        };
+
+        let store = KvStore::open(path).unwrap();
+        assert!(store.lookup(&replay_key("select 1")).is_empty());
+
+        for output in ["AQ==", "Ag=="] {
+            store.record(entry(output)).unwrap();
+        }
+        store
+            .record(ReplayLogEntry {
+                last_command: "select 2".to_string(),
+                ..entry("Aw==")
+            })
+            .unwrap();
+        drop(store);
+
+        let store = KvStore::open(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            vec![entry("AQ=="), entry("Ag==")],
+            store.lookup(&replay_key("select 1"))
+        );
+        assert_eq!(1, store.lookup(&replay_key("select 2")).len());
+    }
+
+    #[test]
+    fn test_undecodable_entries_skipped() {
+        let path = env::temp_dir().join(format!("mocksysql-skip-{}.redb", std::process::id()));
+        let path = path.to_str().unwrap();
+        let store = KvStore::open(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        store
+            .record(ReplayLogEntry {
+                last_command: "select 1".to_string(),
+                response: vec![ResponsePart::Packet("AQ==".to_string())],
+            })
+            .unwrap();
+
+        let transaction = store.database.begin_write().unwrap();
+        transaction
+            .open_table(ENTRIES)
+            .unwrap()
+            .insert((replay_key("select 1").as_str(), 1), "{")
+            .unwrap();
+        transaction.commit().unwrap();
+
+        assert_eq!(1, store.lookup(&replay_key("select 1")).len());
+    }
+}
@@ -0,0 +1,30 @@
+use crate::replay::{ReplayLogEntry, ReplayStore};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Keeps responses in memory, for the lifetime of the proxy.
+#[derive(Default)]
+pub struct MemoryStore {
+    responses: Mutex<HashMap<String, Vec<ReplayLogEntry>>>,
+}
+
+impl ReplayStore for MemoryStore {
+    fn record(&self, entry: ReplayLogEntry) -> Result<(), String> {
+        self.responses
+            .lock()
+            .unwrap()
+            .entry(entry.key())
+            .or_default()
+            .push(entry);
+        Ok(())
+    }
+
+    fn lookup(&self, key: &str) -> Vec<ReplayLogEntry> {
+        self.responses
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,162 @@
+use crate::mysql::command::Command;
+use crate::stats::{self, STATS};
+use log::{error, warn};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// What to do when no response was recorded for a command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MissPolicy {
+    /// Send the command to the server.
+    Forward,
+    /// Answer with an ERR packet, see `REPLAY_MISS_ERROR_CODE` and `REPLAY_MISS_ERROR_MESSAGE`.
+    Error,
+    /// Wait up to the given number of milliseconds for the response to be recorded, e.g, by another proxy feeding
+    /// the Kafka topic, then answer with an ERR packet.
+    Wait(u64),
+    /// Close the client connection.
+    Fail,
+}
+
+impl FromStr for MissPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().split_once(':') {
+            Some(("wait", milliseconds)) => milliseconds
+                .parse()
+                .map(MissPolicy::Wait)
+                .map_err(|_| format!("{} is not a number of milliseconds", milliseconds)),
+            Some(_) => Err(format!("Unknown replay miss policy {}", s)),
+            None => match s.to_lowercase().as_str() {
+                "forward" => Ok(MissPolicy::Forward),
+                "error" => Ok(MissPolicy::Error),
+                "fail" => Ok(MissPolicy::Fail),
+                _ => Err(format!("Unknown replay miss policy {}", s)),
+            },
+        }
+    }
+}
+
+/// Policy of commands without a rule of their own.
+static REPLAY_MISS_POLICY: Lazy<MissPolicy> = Lazy::new(|| match env::var("REPLAY_MISS_POLICY") {
+    Ok(policy) => MissPolicy::from_str(&policy).unwrap_or_else(|e| {
+        error!("Invalid REPLAY_MISS_POLICY ({}), using error", e);
+        MissPolicy::Error
+    }),
+    Err(_) => MissPolicy::Error,
+});
+
+/// Rules overriding the policy by the first keyword of the command, from `REPLAY_MISS_POLICY_*` variables.
+static REPLAY_MISS_RULES: Lazy<Vec<(String, MissPolicy)>> = Lazy::new(|| {
+    env::vars()
+        .filter_map(|(key, value)| {
+            let command = key.strip_prefix("REPLAY_MISS_POLICY_")?;
+            match MissPolicy::from_str(&value) {
+                Ok(policy) => Some((command.to_uppercase(), policy)),
+                Err(e) => {
+                    error!("Ignoring {}, {}", key, e);
+                    None
+                }
+            }
+        })
+        .collect()
+});
+
+/// ER_UNKNOWN_ERROR by default.
+pub static REPLAY_MISS_ERROR_CODE: Lazy<u16> = Lazy::new(|| {
+    env::var("REPLAY_MISS_ERROR_CODE")
+        .ok()
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(1105)
+});
+
+pub static REPLAY_MISS_ERROR_MESSAGE: Lazy<String> = Lazy::new(|| {
+    env::var("REPLAY_MISS_ERROR_MESSAGE")
+        .unwrap_or_else(|_| "No recorded response for the query".to_string())
+});
+
+/// JSONL file every command without a recorded response is appended to, once per replay key. The report is
+/// disabled if unset or empty.
+static REPLAY_MISSING_FIXTURES: Lazy<String> =
+    Lazy::new(|| env::var("REPLAY_MISSING_FIXTURES").unwrap_or_default());
+
+/// Replay keys already written to the report.
+static REPORTED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Serialize)]
+struct MissingFixture<'a> {
+    last_command: &'a str,
+    key: &'a str,
+}
+
+/// Policy applying to the command.
+pub fn policy(command: &Command) -> MissPolicy {
+    rule(&REPLAY_MISS_RULES, command).unwrap_or(*REPLAY_MISS_POLICY)
+}
+
+fn rule(rules: &[(String, MissPolicy)], command: &Command) -> Option<MissPolicy> {
+    let keyword = command.arg.split_whitespace().next()?.to_uppercase();
+    rules
+        .iter()
+        .find(|(command, _)| *command == keyword)
+        .map(|(_, policy)| *policy)
+}
+
+/// Records that no response was found for the command, so it can be recorded later.
+pub fn report(command: &Command, key: &str) {
+    warn!("No recorded response for {}", command.arg);
+    stats::increment(&STATS.replay_misses);
+
+    if REPLAY_MISSING_FIXTURES.is_empty() || !REPORTED.lock().unwrap().insert(key.to_string()) {
+        return;
+    }
+
+    let line = serde_json::to_string(&MissingFixture {
+        last_command: &command.arg,
+        key,
+    })
+    .unwrap();
+
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(REPLAY_MISSING_FIXTURES.as_str())
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(e) = written {
+        error!("Unable to write to {}: {}", *REPLAY_MISSING_FIXTURES, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mysql::command::{Command, MySqlCommand};
+    use crate::replay::miss::{rule, MissPolicy};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_miss_policy() {
+        assert_eq!(Ok(MissPolicy::Forward), MissPolicy::from_str("FORWARD"));
+        assert_eq!(Ok(MissPolicy::Wait(250)), MissPolicy::from_str("wait:250"));
+        assert!(MissPolicy::from_str("wait:soon").is_err());
+        assert!(MissPolicy::from_str("retry").is_err());
+
+        let rules = vec![
+            ("SELECT".to_string(), MissPolicy::Forward),
+            ("SHOW".to_string(), MissPolicy::Fail),
+        ];
+        let command = |query: &str| Command::from_bytes(MySqlCommand::ComQuery, query.as_bytes());
+
+        assert_eq!(
+            Some(MissPolicy::Forward),
+            rule(&rules, &command("  select * from account"))
+        );
+        assert_eq!(None, rule(&rules, &command("delete from account")));
+    }
+}
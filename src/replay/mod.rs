@@ -0,0 +1,185 @@
+use crate::replay::response::ResponsePart;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::Arc;
+
+pub mod file;
+pub mod fingerprint;
+pub mod kafka;
+pub mod kv;
+pub mod memory;
+pub mod miss;
+pub mod readable;
+pub mod response;
+pub mod sequence;
+pub mod session;
+
+/// Response to a command, as recorded by the proxy.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "StoredEntry")]
+pub struct ReplayLogEntry {
+    pub last_command: String,
+    pub response: Vec<ResponsePart>,
+}
+
+/// Forms an entry may have been stored in.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredEntry {
+    Parts {
+        last_command: String,
+        response: Vec<ResponsePart>,
+    },
+    /// Base64 of the raw frames sent by the server, as recorded by earlier versions.
+    Frames {
+        last_command: String,
+        output: String,
+    },
+}
+
+impl TryFrom<StoredEntry> for ReplayLogEntry {
+    type Error = String;
+
+    fn try_from(entry: StoredEntry) -> Result<Self, Self::Error> {
+        match entry {
+            StoredEntry::Parts {
+                last_command,
+                response,
+            } => Ok(ReplayLogEntry {
+                last_command,
+                response,
+            }),
+            StoredEntry::Frames {
+                last_command,
+                output,
+            } => {
+                let frames = STANDARD.decode(output).map_err(|e| e.to_string())?;
+                Ok(ReplayLogEntry {
+                    last_command,
+                    response: response::split_frames(&frames)
+                        .iter()
+                        .map(|payload| ResponsePart::Packet(STANDARD.encode(payload)))
+                        .collect(),
+                })
+            }
+        }
+    }
+}
+
+impl ReplayLogEntry {
+    /// Key the response is stored under, see [`fingerprint::replay_key`].
+    pub fn key(&self) -> String {
+        fingerprint::replay_key(&self.last_command)
+    }
+}
+
+/// Backend responses are recorded to and replayed from.
+pub trait ReplayStore: Send + Sync {
+    fn record(&self, entry: ReplayLogEntry) -> Result<(), String>;
+
+    /// Responses recorded under the key of a command, in the order they were recorded.
+    fn lookup(&self, key: &str) -> Vec<ReplayLogEntry>;
+}
+
+/**
+Replay store shared by every connection, present only if the corresponding mode (recording or replaying) is enabled.
+*/
+pub type ReplayLog = Option<Arc<dyn ReplayStore>>;
+
+/// Backend of the replay store, one of `kafka`, `file`, `memory` or `kv`.
+static REPLAY_STORE: Lazy<String> =
+    Lazy::new(|| env::var("REPLAY_STORE").unwrap_or_else(|_| "kafka".to_string()));
+
+/// JSONL file used by the `file` store.
+static REPLAY_FILE: Lazy<String> =
+    Lazy::new(|| env::var("REPLAY_FILE").unwrap_or_else(|_| "replay.jsonl".to_string()));
+
+/// Database file used by the `kv` store.
+static REPLAY_KV_PATH: Lazy<String> =
+    Lazy::new(|| env::var("REPLAY_KV_PATH").unwrap_or_else(|_| "replay.redb".to_string()));
+
+/// Whether responses from the server are recorded.
+pub fn recording_enabled() -> bool {
+    flag_enabled("replay_log_enable") || flag_enabled("kafka_replay_log_enable")
+}
+
+/// Whether responses are served from the replay store instead of the server.
+pub fn replaying_enabled() -> bool {
+    flag_enabled("replay_response_enable") || flag_enabled("kafka_replay_response_enable")
+}
+
+/// Whether the proxy impersonates the server from the sessions recorded to `REPLAY_SESSION_FILE`, without connecting
+/// to it.
+pub fn offline_enabled() -> bool {
+    flag_enabled("REPLAY_OFFLINE")
+}
+
+fn flag_enabled(name: &str) -> bool {
+    env::var(name).is_ok_and(|val| val == "true")
+}
+
+/// Opens the configured store, returning the stores used for recording and for replaying respectively.
+pub fn open_stores() -> Result<(ReplayLog, ReplayLog), String> {
+    if offline_enabled() {
+        info!("Replaying offline from the recorded sessions");
+        return Ok((
+            None,
+            Some(session::load_offline_session()?.responses.clone()),
+        ));
+    }
+
+    let (record, replay) = (recording_enabled(), replaying_enabled());
+    if !record && !replay {
+        return Ok((None, None));
+    }
+
+    let store: Arc<dyn ReplayStore> = match REPLAY_STORE.as_str() {
+        "kafka" => kafka::KafkaStore::connect(record, replay),
+        "file" => Arc::new(
+            file::FileStore::open(&REPLAY_FILE)
+                .map_err(|e| format!("Unable to open REPLAY_FILE: {}", e))?,
+        ),
+        "memory" => Arc::new(memory::MemoryStore::default()),
+        "kv" => Arc::new(
+            kv::KvStore::open(&REPLAY_KV_PATH)
+                .map_err(|e| format!("Unable to open REPLAY_KV_PATH: {}", e))?,
+        ),
+        store => return Err(format!("Unknown REPLAY_STORE {}", store)),
+    };
+    info!("Using {} replay store", *REPLAY_STORE);
+
+    Ok((
+        Some(store.clone()).filter(|_| record),
+        Some(store).filter(|_| replay),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::response::ResponsePart;
+    use crate::replay::ReplayLogEntry;
+
+    #[test]
+    fn test_legacy_entry() {
+        // OK packet followed by a second packet, framed with their headers.
+        let entry: ReplayLogEntry = serde_json::from_str(
+            r#"{"last_command": "select 1", "output": "BwAAAQAAAAIAAAABAAACAQ=="}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            ReplayLogEntry {
+                last_command: "select 1".to_string(),
+                response: vec![
+                    ResponsePart::Packet("AAAAAgAAAA==".to_string()),
+                    ResponsePart::Packet("AQ==".to_string()),
+                ],
+            },
+            entry
+        );
+    }
+}
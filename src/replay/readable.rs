@@ -0,0 +1,469 @@
+use crate::mysql::accumulator::result_set::{ColumnDefinition, FieldTypes};
+use crate::mysql::accumulator::CapabilityFlags;
+use crate::mysql::packet::{ErrorData, OkData};
+use crate::mysql::types::{Converter, StringLenEnc};
+use crate::replay::response::{ResponsePart, ResultSetEnd};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+/// Fields of an OK packet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OkFields {
+    pub affected_rows: u64,
+    pub last_insert_id: u64,
+    pub status_flags: u16,
+    pub warnings: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
+}
+
+/// Fields of an ERR packet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorFields {
+    pub code: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    pub message: String,
+}
+
+/// Column definition of a result set, without the fields which are always the same.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub org_name: String,
+    pub table: String,
+    pub org_table: String,
+    pub schema: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldTypes,
+    pub flags: u16,
+    pub decimals: u8,
+    pub character_set: u16,
+    pub column_length: u32,
+}
+
+/// Readable form of a recorded part, if it re-encodes to exactly the same packets. Parts which don't, e.g, binary
+/// values or session state tracking, are kept as they are.
+pub fn from_wire(part: &ResponsePart, client_flag: u32) -> Option<ResponsePart> {
+    let readable = match part {
+        ResponsePart::Packet(payload) => {
+            let payload = STANDARD.decode(payload).ok()?;
+            match payload.first() {
+                Some(0x00) => ResponsePart::Ok(OkFields::from_payload(&payload, client_flag)?),
+                Some(0xff) => {
+                    ResponsePart::Error(ErrorFields::from_payload(&payload, client_flag)?)
+                }
+                _ => return None,
+            }
+        }
+        ResponsePart::ResultSet { columns, rows, end } => {
+            let columns = columns
+                .iter()
+                .map(|column| Column::from_payload(&STANDARD.decode(column).ok()?))
+                .collect::<Option<Vec<_>>>()?;
+            let rows = rows
+                .iter()
+                .map(|row| parse_row(&STANDARD.decode(row).ok()?, &columns))
+                .collect::<Option<Vec<_>>>()?;
+            let (warnings, status_flags, error) = match end {
+                ResultSetEnd::Status {
+                    warnings,
+                    status_flags,
+                } => (*warnings, *status_flags, None),
+                ResultSetEnd::Error(payload) => {
+                    let payload = STANDARD.decode(payload).ok()?;
+                    (
+                        0,
+                        0,
+                        Some(ErrorFields::from_payload(&payload, client_flag)?),
+                    )
+                }
+            };
+
+            ResponsePart::Table {
+                columns,
+                rows,
+                warnings,
+                status_flags,
+                error,
+            }
+        }
+        _ => return None,
+    };
+
+    match to_wire(&readable, client_flag) {
+        Ok(wire) if wire == *part => Some(readable),
+        _ => None,
+    }
+}
+
+/// Wire form of a part, replayed as is if it already is.
+pub fn to_wire(part: &ResponsePart, client_flag: u32) -> Result<ResponsePart, String> {
+    Ok(match part {
+        ResponsePart::Ok(ok) => ResponsePart::Packet(STANDARD.encode(ok.to_payload(client_flag))),
+        ResponsePart::Error(error) => {
+            ResponsePart::Packet(STANDARD.encode(error.to_payload(client_flag)))
+        }
+        ResponsePart::Table {
+            columns,
+            rows,
+            warnings,
+            status_flags,
+            error,
+        } => ResponsePart::ResultSet {
+            columns: columns
+                .iter()
+                .map(|column| STANDARD.encode(column.to_payload()))
+                .collect(),
+            rows: rows
+                .iter()
+                .map(|row| encode_row(row).map(|row| STANDARD.encode(row)))
+                .collect::<Result<_, _>>()?,
+            end: match error {
+                Some(error) => ResultSetEnd::Error(STANDARD.encode(error.to_payload(client_flag))),
+                None => ResultSetEnd::Status {
+                    warnings: *warnings,
+                    status_flags: *status_flags,
+                },
+            },
+        },
+        part => part.clone(),
+    })
+}
+
+impl OkFields {
+    fn from_payload(payload: &[u8], client_flag: u32) -> Option<OkFields> {
+        let mut reader = Reader::new(payload);
+        reader.int(1)?;
+
+        let affected_rows = reader.int_lenenc()?;
+        let last_insert_id = reader.int_lenenc()?;
+        let (status_flags, warnings) =
+            if client_flag & CapabilityFlags::ClientProtocol41 as u32 != 0 {
+                (reader.int(2)? as u16, reader.int(2)? as u16)
+            } else if client_flag & CapabilityFlags::ClientTransactions as u32 != 0 {
+                (reader.int(2)? as u16, 0)
+            } else {
+                (0, 0)
+            };
+
+        let info = match reader.is_empty() {
+            true => None,
+            false if client_flag & CapabilityFlags::ClientSessionTrack as u32 != 0 => {
+                Some(reader.string_lenenc()?)
+            }
+            false => Some(String::from_utf8(reader.rest().to_vec()).ok()?),
+        };
+
+        Some(OkFields {
+            affected_rows,
+            last_insert_id,
+            status_flags,
+            warnings,
+            info,
+        })
+    }
+
+    fn to_payload(&self, client_flag: u32) -> Vec<u8> {
+        OkData {
+            header: 0x00,
+            affected_rows: self.affected_rows,
+            last_insert_id: self.last_insert_id,
+            status_flags: Some(self.status_flags),
+            warnings: Some(self.warnings),
+            info: self.info.clone(),
+            session_state_info: None,
+        }
+        .to_packet(0, client_flag)
+        .body
+    }
+}
+
+impl ErrorFields {
+    fn from_payload(payload: &[u8], client_flag: u32) -> Option<ErrorFields> {
+        let mut reader = Reader::new(payload);
+        reader.int(1)?;
+
+        let code = reader.int(2)? as u16;
+        let mut state = None;
+        if client_flag & CapabilityFlags::ClientProtocol41 as u32 != 0
+            && reader.peek() == Some(b'#')
+        {
+            reader.int(1)?;
+            state = Some(String::from_utf8(reader.bytes(5)?.to_vec()).ok()?);
+        }
+
+        Some(ErrorFields {
+            code,
+            state,
+            message: String::from_utf8(reader.rest().to_vec()).ok()?,
+        })
+    }
+
+    fn to_payload(&self, client_flag: u32) -> Vec<u8> {
+        let error = match &self.state {
+            Some(state) => ErrorData::new(self.code, state, self.message.clone()),
+            None => ErrorData {
+                error_code: self.code,
+                sql_state: None,
+                error_message: self.message.clone(),
+            },
+        };
+        error.to_packet(0, client_flag).body
+    }
+}
+
+impl Column {
+    fn from_payload(payload: &[u8]) -> Option<Column> {
+        let mut reader = Reader::new(payload);
+        if reader.string_lenenc()? != "def" {
+            return None;
+        }
+
+        let schema = reader.string_lenenc()?;
+        let table = reader.string_lenenc()?;
+        let org_table = reader.string_lenenc()?;
+        let name = reader.string_lenenc()?;
+        let org_name = reader.string_lenenc()?;
+        // Length of the fixed length fields
+        reader.int_lenenc()?;
+        let character_set = reader.int(2)? as u16;
+        let column_length = reader.int(4)? as u32;
+        let field_type = FieldTypes::try_from(reader.int(1)? as u16).ok()?;
+
+        Some(Column {
+            name,
+            org_name,
+            table,
+            org_table,
+            schema,
+            field_type,
+            flags: reader.int(2)? as u16,
+            decimals: reader.int(1)? as u8,
+            character_set,
+            column_length,
+        })
+    }
+
+    fn to_payload(&self) -> Vec<u8> {
+        ColumnDefinition {
+            catalog: "def".to_string(),
+            schema: self.schema.clone(),
+            table: self.table.clone(),
+            org_table: self.org_table.clone(),
+            name: self.name.clone(),
+            org_name: self.org_name.clone(),
+            fixed_length_fields: 0x0c,
+            character_set: self.character_set,
+            column_length: self.column_length,
+            field_type: self.field_type.clone(),
+            flags: self.flags,
+            decimals: self.decimals,
+            reserved: 0,
+        }
+        .to_packet(0)
+        .body
+    }
+}
+
+/// Values of a text result set row, as numbers when the column is numeric and the number is written the same way.
+fn parse_row(payload: &[u8], columns: &[Column]) -> Option<Vec<Value>> {
+    let mut reader = Reader::new(payload);
+    let mut row = vec![];
+
+    for column in columns {
+        if reader.peek() == Some(0xfb) {
+            reader.int(1)?;
+            row.push(Value::Null);
+        } else {
+            row.push(typed(reader.string_lenenc()?, &column.field_type));
+        }
+    }
+
+    reader.is_empty().then_some(row)
+}
+
+fn typed(text: String, field_type: &FieldTypes) -> Value {
+    let number = match field_type {
+        FieldTypes::MysqlTypeTiny
+        | FieldTypes::MysqlTypeShort
+        | FieldTypes::MysqlTypeLong
+        | FieldTypes::MysqlTypeLongLong
+        | FieldTypes::MysqlTypeInt24
+        | FieldTypes::MysqlTypeYear => text
+            .parse::<i64>()
+            .map(Number::from)
+            .or_else(|_| text.parse::<u64>().map(Number::from))
+            .ok(),
+        FieldTypes::MysqlTypeFloat | FieldTypes::MysqlTypeDouble => {
+            text.parse::<f64>().ok().and_then(Number::from_f64)
+        }
+        // Decimals are kept as strings so as not to lose precision.
+        _ => None,
+    };
+
+    match number {
+        // e.g, ZEROFILL columns
+        Some(number) if number.to_string() == text => Value::Number(number),
+        _ => Value::String(text),
+    }
+}
+
+fn encode_row(row: &[Value]) -> Result<Vec<u8>, String> {
+    let mut payload = vec![];
+
+    for value in row {
+        let text = match value {
+            Value::Null => {
+                payload.push(0xfb);
+                continue;
+            }
+            Value::Bool(value) => (*value as u8).to_string(),
+            Value::Number(number) => number.to_string(),
+            Value::String(text) => text.clone(),
+            value => return Err(format!("Unsupported value in a row: {}", value)),
+        };
+        payload.extend(StringLenEnc::encode(text, None));
+    }
+
+    Ok(payload)
+}
+
+/// Reads the fields of a payload in order, returning `None` rather than panicking when it is too short.
+struct Reader<'a> {
+    payload: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(payload: &'a [u8]) -> Reader<'a> {
+        Reader { payload, offset: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.offset >= self.payload.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.payload.get(self.offset).copied()
+    }
+
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.payload.get(self.offset..self.offset + length)?;
+        self.offset += length;
+        Some(bytes)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = self.payload.get(self.offset..).unwrap_or_default();
+        self.offset = self.payload.len();
+        rest
+    }
+
+    fn int(&mut self, length: usize) -> Option<u64> {
+        let mut buffer = [0u8; 8];
+        buffer[..length].copy_from_slice(self.bytes(length)?);
+        Some(u64::from_le_bytes(buffer))
+    }
+
+    fn int_lenenc(&mut self) -> Option<u64> {
+        match self.int(1)? {
+            0xfc => self.int(2),
+            0xfd => self.int(3),
+            0xfe => self.int(8),
+            0xfb | 0xff => None,
+            value => Some(value),
+        }
+    }
+
+    fn string_lenenc(&mut self) -> Option<String> {
+        let length = self.int_lenenc()? as usize;
+        String::from_utf8(self.bytes(length)?.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mysql::accumulator::result_set::FieldTypes;
+    use crate::mysql::accumulator::CapabilityFlags;
+    use crate::mysql::packet::ErrorData;
+    use crate::replay::readable::{from_wire, to_wire, typed, Column, ErrorFields};
+    use crate::replay::response::{ResponsePart, ResultSetEnd};
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_readable_round_trip() {
+        let client_flag = CapabilityFlags::ClientProtocol41 as u32;
+        let column = |name: &str, field_type: FieldTypes| Column {
+            name: name.to_string(),
+            org_name: name.to_string(),
+            table: "account".to_string(),
+            org_table: "account".to_string(),
+            schema: "shop".to_string(),
+            field_type,
+            flags: 0,
+            decimals: 0,
+            character_set: 255,
+            column_length: 20,
+        };
+        let wire = ResponsePart::ResultSet {
+            columns: vec![
+                STANDARD.encode(column("id", FieldTypes::MysqlTypeLongLong).to_payload()),
+                STANDARD.encode(column("name", FieldTypes::MysqlTypeVarString).to_payload()),
+            ],
+            rows: vec![
+                STANDARD.encode(b"\x017\x04Zo\xc3\xab"),
+                STANDARD.encode(b"\x03007\xfb"),
+            ],
+            end: ResultSetEnd::Status {
+                warnings: 0,
+                status_flags: 0x02,
+            },
+        };
+
+        let readable = from_wire(&wire, client_flag).unwrap();
+        let json = serde_json::to_value(&readable).unwrap();
+        assert_eq!("MYSQL_TYPE_LONG_LONG", json["table"]["columns"][0]["type"]);
+        assert_eq!(json!([[7, "Zoë"], ["007", null]]), json["table"]["rows"]);
+        assert_eq!(wire, to_wire(&readable, client_flag).unwrap());
+
+        let error = ErrorData::new(1146, "42S02", "Table 'shop.cart' doesn't exist".to_string())
+            .to_packet(1, client_flag);
+        let readable = from_wire(
+            &ResponsePart::Packet(STANDARD.encode(&error.body)),
+            client_flag,
+        );
+        assert_eq!(
+            Some(ResponsePart::Error(ErrorFields {
+                code: 1146,
+                state: Some("42S02".to_string()),
+                message: "Table 'shop.cart' doesn't exist".to_string(),
+            })),
+            readable
+        );
+
+        // Binary values are left as they are.
+        let binary = ResponsePart::ResultSet {
+            columns: vec![STANDARD.encode(column("data", FieldTypes::MysqlTypeBlob).to_payload())],
+            rows: vec![STANDARD.encode(b"\x02\xff\xfe")],
+            end: ResultSetEnd::Status {
+                warnings: 0,
+                status_flags: 0x02,
+            },
+        };
+        assert_eq!(None, from_wire(&binary, client_flag));
+
+        assert_eq!(
+            json!(1.5),
+            typed("1.5".to_string(), &FieldTypes::MysqlTypeDouble)
+        );
+        assert_eq!(
+            Value::String("1.50".to_string()),
+            typed("1.50".to_string(), &FieldTypes::MysqlTypeNewDecimal)
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use crate::mysql::accumulator::CapabilityFlags;
+use crate::mysql::packet::{EofData, OkData, Packet, PacketHeader, PacketType};
+use crate::mysql::types::{Converter, IntFixedLen, IntLenEnc};
+use crate::replay::readable::{self, Column, ErrorFields, OkFields};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::error;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+
+/// Payloads of this size continue in the next packet.
+const MAX_PAYLOAD_SIZE: usize = 0xffffff;
+
+/// How responses are recorded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Packets as base64 payloads.
+    Wire,
+    /// OK and ERR fields, column definitions and typed rows, to be reviewed and edited by hand. Packets which
+    /// can't be decoded without loss are kept as base64 payloads.
+    Readable,
+}
+
+static REPLAY_FORMAT: Lazy<Format> = Lazy::new(|| {
+    match env::var("REPLAY_FORMAT")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "" | "wire" => Format::Wire,
+        "readable" => Format::Readable,
+        format => {
+            error!("Unknown REPLAY_FORMAT {}, using wire", format);
+            Format::Wire
+        }
+    }
+});
+
+/// Part of a recorded response, decoded from the packets sent by the server so that it can be re-encoded for a
+/// session with other capabilities. Payloads are base64 encoded, without their packet header.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponsePart {
+    /// Packet replayed as is, e.g, an OK or ERR packet.
+    Packet(String),
+    /// Text result set, replayed with or without the EOF packets depending on CLIENT_DEPRECATE_EOF.
+    ResultSet {
+        columns: Vec<String>,
+        rows: Vec<String>,
+        end: ResultSetEnd,
+    },
+    /// OK packet, in the readable format.
+    Ok(OkFields),
+    /// ERR packet, in the readable format.
+    Error(ErrorFields),
+    /// Text result set, in the readable format. An error interrupting the rows replaces the status.
+    Table {
+        columns: Vec<Column>,
+        rows: Vec<Vec<Value>>,
+        warnings: u16,
+        status_flags: u16,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<ErrorFields>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultSetEnd {
+    /// Sent as an EOF packet, or as an OK packet to clients with CLIENT_DEPRECATE_EOF.
+    Status { warnings: u16, status_flags: u16 },
+    /// ERR packet interrupting the rows.
+    Error(String),
+}
+
+/// Splits frames sent by the server into the payloads of their logical packets, joining payloads split across
+/// several packets.
+pub fn split_frames(bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut payloads: Vec<Vec<u8>> = vec![];
+    let mut continued = false;
+    let mut offset = 0;
+
+    while offset + 4 <= bytes.len() {
+        let header = PacketHeader::from_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let end = (offset + 4 + header.size).min(bytes.len());
+        let payload = &bytes[offset + 4..end];
+
+        match payloads.last_mut() {
+            Some(last) if continued => last.extend_from_slice(payload),
+            _ => payloads.push(payload.to_vec()),
+        }
+
+        continued = header.size == MAX_PAYLOAD_SIZE;
+        offset = end;
+    }
+
+    payloads
+}
+
+/// Decodes the payloads of a response sent to a client with the given capabilities, in the `REPLAY_FORMAT`.
+/// Responses which aren't made of OK/ERR packets and text result sets are kept as a list of packets.
+pub fn decode(payloads: &[Vec<u8>], client_flag: u32) -> Vec<ResponsePart> {
+    let parts = decode_results(payloads, client_flag).unwrap_or_else(|| {
+        payloads
+            .iter()
+            .map(|payload| ResponsePart::Packet(STANDARD.encode(payload)))
+            .collect()
+    });
+
+    match *REPLAY_FORMAT {
+        Format::Wire => parts,
+        Format::Readable => parts
+            .into_iter()
+            .map(|part| readable::from_wire(&part, client_flag).unwrap_or(part))
+            .collect(),
+    }
+}
+
+fn decode_results(payloads: &[Vec<u8>], client_flag: u32) -> Option<Vec<ResponsePart>> {
+    let deprecate_eof = client_flag & CapabilityFlags::ClientDeprecateEof as u32 != 0;
+    let optional_metadata =
+        client_flag & CapabilityFlags::ClientOptionalResultSetMetadata as u32 != 0;
+
+    let mut parts = vec![];
+    let mut payloads = payloads.iter();
+
+    while let Some(payload) = payloads.next() {
+        // OK, ERR or LOCAL INFILE request.
+        if matches!(payload.first(), None | Some(0x00 | 0xfb | 0xfe | 0xff)) {
+            parts.push(ResponsePart::Packet(STANDARD.encode(payload)));
+            continue;
+        }
+
+        let mut column_count: &[u8] = payload;
+        if optional_metadata {
+            // Columns are only replayed if their definitions were sent.
+            match column_count.split_first() {
+                Some((0x01, rest)) if !rest.is_empty() => column_count = rest,
+                _ => return None,
+            }
+        }
+        let column_count = IntLenEnc::from_bytes(&column_count.to_vec(), None).result;
+
+        let mut columns = vec![];
+        for _ in 0..column_count {
+            columns.push(STANDARD.encode(payloads.next()?));
+        }
+
+        if !deprecate_eof && !is_eof(payloads.next()?) {
+            return None;
+        }
+
+        let mut rows = vec![];
+        let end = loop {
+            let payload = payloads.next()?;
+
+            if payload.first() == Some(&0xff) {
+                break ResultSetEnd::Error(STANDARD.encode(payload));
+            }
+
+            match deprecate_eof {
+                true if payload.first() == Some(&0xfe) && payload.len() < MAX_PAYLOAD_SIZE => {
+                    break ok_status(payload)?
+                }
+                false if is_eof(payload) => break eof_status(payload),
+                _ => rows.push(STANDARD.encode(payload)),
+            }
+        };
+
+        parts.push(ResponsePart::ResultSet { columns, rows, end });
+    }
+
+    Some(parts)
+}
+
+fn is_eof(payload: &[u8]) -> bool {
+    payload.first() == Some(&0xfe) && payload.len() <= 5
+}
+
+fn eof_status(payload: &[u8]) -> ResultSetEnd {
+    let field = |offset: usize| match payload.get(offset..offset + 2) {
+        Some(bytes) => IntFixedLen::from_bytes(&bytes.to_vec(), Some(2)).result as u16,
+        None => 0,
+    };
+
+    ResultSetEnd::Status {
+        warnings: field(1),
+        status_flags: field(3),
+    }
+}
+
+/// Status of the OK packet ending a result set sent to a client with CLIENT_DEPRECATE_EOF.
+fn ok_status(payload: &[u8]) -> Option<ResultSetEnd> {
+    let mut offset = 1;
+    for _ in 0..2 {
+        // Affected rows and last insert id
+        offset += IntLenEnc::from_bytes(&payload.get(offset..)?.to_vec(), None).offset_increment;
+    }
+
+    let field = |offset: usize| {
+        payload
+            .get(offset..offset + 2)
+            .map(|bytes| IntFixedLen::from_bytes(&bytes.to_vec(), Some(2)).result as u16)
+    };
+
+    Some(ResultSetEnd::Status {
+        status_flags: field(offset).unwrap_or(0),
+        warnings: field(offset + 2).unwrap_or(0),
+    })
+}
+
+/// Encodes the response for a client with the given capabilities, numbering its packets from `sequence` onwards.
+/// Parts can be in either format.
+pub fn encode(parts: &[ResponsePart], sequence: u8, client_flag: u32) -> Result<Vec<u8>, String> {
+    let deprecate_eof = client_flag & CapabilityFlags::ClientDeprecateEof as u32 != 0;
+    let decode = |payload: &String| STANDARD.decode(payload).map_err(|e| e.to_string());
+
+    let mut payloads: Vec<Vec<u8>> = vec![];
+
+    for part in parts {
+        let (columns, rows, end) = match readable::to_wire(part, client_flag)? {
+            ResponsePart::Packet(payload) => {
+                payloads.push(decode(&payload)?);
+                continue;
+            }
+            ResponsePart::ResultSet { columns, rows, end } => (columns, rows, end),
+            part => return Err(format!("Unexpected response part {:?}", part)),
+        };
+
+        let mut column_count = vec![];
+        if client_flag & CapabilityFlags::ClientOptionalResultSetMetadata as u32 != 0 {
+            // RESULTSET_METADATA_FULL
+            column_count.push(0x01);
+        }
+        column_count.extend(IntLenEnc::encode(columns.len() as u64, None));
+        payloads.push(column_count);
+
+        for column in &columns {
+            payloads.push(decode(column)?);
+        }
+
+        let (warnings, status_flags) = match &end {
+            ResultSetEnd::Status {
+                warnings,
+                status_flags,
+            } => (*warnings, *status_flags),
+            ResultSetEnd::Error(_) => (0, 0),
+        };
+
+        if !deprecate_eof {
+            let eof = EofData {
+                status_flags: Some(status_flags),
+                warnings: Some(warnings),
+            };
+            payloads.push(eof.to_packet(0, client_flag).body);
+        }
+
+        for row in &rows {
+            payloads.push(decode(row)?);
+        }
+
+        payloads.push(match end {
+            ResultSetEnd::Error(payload) => decode(&payload)?,
+            ResultSetEnd::Status { .. } if deprecate_eof => {
+                OkData {
+                    header: 0xfe,
+                    affected_rows: 0,
+                    last_insert_id: 0,
+                    status_flags: Some(status_flags),
+                    warnings: Some(warnings),
+                    info: None,
+                    session_state_info: None,
+                }
+                .to_packet(0, client_flag)
+                .body
+            }
+            ResultSetEnd::Status { .. } => {
+                EofData {
+                    status_flags: Some(status_flags),
+                    warnings: Some(warnings),
+                }
+                .to_packet(0, client_flag)
+                .body
+            }
+        });
+    }
+
+    Ok(frame(payloads, sequence))
+}
+
+/// Wraps the payloads in packets numbered from `sequence` onwards, splitting payloads too large for a single packet.
+pub fn frame(payloads: Vec<Vec<u8>>, sequence: u8) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut sequence = sequence;
+
+    for payload in payloads {
+        let mut chunks: Vec<&[u8]> = payload.chunks(MAX_PAYLOAD_SIZE).collect();
+        if payload.len() % MAX_PAYLOAD_SIZE == 0 {
+            // An empty packet ends payloads which are a multiple of the maximum size, or empty ones.
+            chunks.push(&[]);
+        }
+
+        for chunk in chunks {
+            let packet = Packet {
+                header: PacketHeader {
+                    size: chunk.len(),
+                    seq: sequence,
+                },
+                body: chunk.to_vec(),
+                p_type: PacketType::Other,
+                skip: false,
+            };
+            bytes.extend(packet.to_bytes());
+            sequence = sequence.wrapping_add(1);
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mysql::accumulator::CapabilityFlags;
+    use crate::mysql::text_result_set::TextResultSet;
+    use crate::replay::response::{decode, encode, split_frames, ResponsePart, ResultSetEnd};
+
+    #[test]
+    fn test_reencode_result_set() {
+        let mut result_set = TextResultSet::new(&["id"]);
+        result_set.push_row(vec![Some("1".to_string())]);
+
+        let client_flag = CapabilityFlags::ClientProtocol41 as u32;
+        let deprecate_eof = client_flag | CapabilityFlags::ClientDeprecateEof as u32;
+        let frames = |sequence: u8, client_flag: u32| -> Vec<u8> {
+            result_set
+                .to_packets(sequence, client_flag)
+                .iter()
+                .flat_map(|packet| packet.to_bytes())
+                .collect()
+        };
+
+        // Recorded with EOF packets at sequence 3, replayed without them at sequence 1, and back.
+        let parts = decode(&split_frames(&frames(3, client_flag)), client_flag);
+        match &parts[..] {
+            [ResponsePart::ResultSet { columns, rows, end }] => {
+                assert_eq!((1, 1), (columns.len(), rows.len()));
+                assert_eq!(
+                    &ResultSetEnd::Status {
+                        warnings: 0,
+                        status_flags: 0x02
+                    },
+                    end
+                );
+            }
+            parts => panic!("Unexpected response {:?}", parts),
+        }
+        assert_eq!(
+            frames(1, deprecate_eof),
+            encode(&parts, 1, deprecate_eof).unwrap()
+        );
+
+        let parts = decode(&split_frames(&frames(1, deprecate_eof)), deprecate_eof);
+        assert_eq!(
+            frames(1, client_flag),
+            encode(&parts, 1, client_flag).unwrap()
+        );
+
+        // Unknown responses are resequenced as they are.
+        let parts = decode(&[vec![0x01], vec![]], client_flag);
+        assert_eq!(
+            vec![1, 0, 0, 4, 0x01, 0, 0, 0, 5],
+            encode(&parts, 4, client_flag).unwrap()
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use dashmap::DashMap;
+use log::error;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::env;
+
+/// Which replays of a query advance through its recorded responses together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    /// Every connection starts from the first recorded response.
+    Connection,
+    /// Connections share their position, e.g, a query polled from a pool of connections.
+    Session,
+}
+
+/// What to serve once every recorded response to a query has been served.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exhausted {
+    /// Keep serving the last response.
+    Repeat,
+    /// Start again from the first response.
+    Cycle,
+    /// Nothing, the query is handled as a miss.
+    Error,
+}
+
+static REPLAY_SEQUENCE_SCOPE: Lazy<Scope> = Lazy::new(|| {
+    match env::var("REPLAY_SEQUENCE_SCOPE")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "" | "connection" => Scope::Connection,
+        "session" => Scope::Session,
+        scope => {
+            error!("Unknown REPLAY_SEQUENCE_SCOPE {}, using connection", scope);
+            Scope::Connection
+        }
+    }
+});
+
+static REPLAY_SEQUENCE_EXHAUSTED: Lazy<Exhausted> = Lazy::new(|| {
+    match env::var("REPLAY_SEQUENCE_EXHAUSTED")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "" | "repeat" => Exhausted::Repeat,
+        "cycle" => Exhausted::Cycle,
+        "error" => Exhausted::Error,
+        exhausted => {
+            error!(
+                "Unknown REPLAY_SEQUENCE_EXHAUSTED {}, using repeat",
+                exhausted
+            );
+            Exhausted::Repeat
+        }
+    }
+});
+
+/// Number of responses served by replay key, shared by every connection when in the session scope.
+static SESSION_POSITIONS: Lazy<DashMap<String, usize>> = Lazy::new(DashMap::new);
+
+/// Takes the next of the responses recorded under the key, advancing the position of the connection, or of the
+/// session, depending on `REPLAY_SEQUENCE_SCOPE`.
+pub fn next_response<T: Clone>(
+    responses: &[T],
+    key: &str,
+    positions: &mut HashMap<String, usize>,
+) -> Option<T> {
+    match *REPLAY_SEQUENCE_SCOPE {
+        Scope::Connection => advance(responses, positions.entry(key.to_string()).or_default()),
+        Scope::Session => advance(
+            responses,
+            &mut SESSION_POSITIONS.entry(key.to_string()).or_default(),
+        ),
+    }
+}
+
+fn advance<T: Clone>(responses: &[T], position: &mut usize) -> Option<T> {
+    let response = select(responses, *position, *REPLAY_SEQUENCE_EXHAUSTED)?;
+    *position += 1;
+    Some(response.clone())
+}
+
+fn select<T>(responses: &[T], position: usize, exhausted: Exhausted) -> Option<&T> {
+    if responses.is_empty() {
+        return None;
+    }
+
+    match responses.get(position) {
+        Some(response) => Some(response),
+        None => match exhausted {
+            Exhausted::Repeat => responses.last(),
+            Exhausted::Cycle => responses.get(position % responses.len()),
+            Exhausted::Error => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::sequence::{select, Exhausted};
+
+    #[test]
+    fn test_select() {
+        let responses = vec!["AQ==".to_string(), "Ag==".to_string()];
+        let served = |exhausted: Exhausted| {
+            (0..5)
+                .map(|position| select(&responses, position, exhausted).map(String::as_str))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![
+                Some("AQ=="),
+                Some("Ag=="),
+                Some("Ag=="),
+                Some("Ag=="),
+                Some("Ag==")
+            ],
+            served(Exhausted::Repeat)
+        );
+        assert_eq!(
+            vec![
+                Some("AQ=="),
+                Some("Ag=="),
+                Some("AQ=="),
+                Some("Ag=="),
+                Some("AQ==")
+            ],
+            served(Exhausted::Cycle)
+        );
+        assert_eq!(
+            vec![Some("AQ=="), Some("Ag=="), None, None, None],
+            served(Exhausted::Error)
+        );
+        assert_eq!(None, select::<String>(&[], 0, Exhausted::Repeat));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::mysql::accumulator::CapabilityFlags;
+use crate::mysql::types::{Converter, IntFixedLen};
+use crate::replay::memory::MemoryStore;
+use crate::replay::{recording_enabled, ReplayLogEntry, ReplayStore};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::error;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// JSONL file whole sessions are recorded to, and replayed from when offline. An empty value disables it.
+static REPLAY_SESSION_FILE: Lazy<String> =
+    Lazy::new(|| env::var("REPLAY_SESSION_FILE").unwrap_or_default());
+
+/// Open while recording, if `REPLAY_SESSION_FILE` is set.
+static SESSION_RECORDING: Lazy<Option<Mutex<File>>> = Lazy::new(|| {
+    if !recording_enabled() || REPLAY_SESSION_FILE.is_empty() {
+        return None;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(REPLAY_SESSION_FILE.as_str())
+        .expect("Unable to open REPLAY_SESSION_FILE");
+    Some(Mutex::new(file))
+});
+
+/// Recorded sessions, loaded at startup when replaying offline (see [`load_offline_session`]).
+pub static OFFLINE_SESSION: OnceCell<Session> = OnceCell::new();
+
+/// Packet exchanged by a recorded connection, payloads being base64 encoded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// Initial handshake packet of the server.
+    Greeting { connection: u64, payload: String },
+    /// Packet sent by the client or the server while authenticating.
+    Auth {
+        connection: u64,
+        from_server: bool,
+        payload: String,
+    },
+    /// Command of the client and the response of the server.
+    Command {
+        connection: u64,
+        #[serde(flatten)]
+        entry: ReplayLogEntry,
+    },
+}
+
+impl SessionEvent {
+    pub fn packet(connection: u64, from_server: bool, greeting: bool, payload: &[u8]) -> Self {
+        let payload = STANDARD.encode(payload);
+        match greeting {
+            true => SessionEvent::Greeting {
+                connection,
+                payload,
+            },
+            false => SessionEvent::Auth {
+                connection,
+                from_server,
+                payload,
+            },
+        }
+    }
+}
+
+/// Whether sessions are recorded.
+pub fn recording() -> bool {
+    SESSION_RECORDING.is_some()
+}
+
+/// Appends the event to the session recording, if enabled.
+pub fn record(event: SessionEvent) {
+    let file = match SESSION_RECORDING.as_ref() {
+        Some(file) => file,
+        None => return,
+    };
+
+    let written = serde_json::to_string(&event)
+        .map_err(|e| e.to_string())
+        .and_then(|line| writeln!(file.lock().unwrap(), "{}", line).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        error!("Unable to record session event, {}", e);
+    }
+}
+
+/// Loads the sessions recorded to `REPLAY_SESSION_FILE`, failing if none can be replayed from it.
+pub fn load_offline_session() -> Result<&'static Session, String> {
+    OFFLINE_SESSION
+        .get_or_try_init(|| match REPLAY_SESSION_FILE.is_empty() {
+            true => Err("REPLAY_SESSION_FILE is not set".to_string()),
+            false => Session::load(&REPLAY_SESSION_FILE),
+        })
+        .map_err(|e| format!("Unable to load REPLAY_SESSION_FILE, {}", e))
+}
+
+/// Sessions the proxy impersonates the server from: the greeting sent to new clients and the responses to commands.
+pub struct Session {
+    greeting: Vec<u8>,
+    pub responses: Arc<MemoryStore>,
+}
+
+impl Session {
+    pub fn load(path: &str) -> Result<Session, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut greeting = None;
+        let responses = MemoryStore::default();
+
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<SessionEvent>(&line) {
+                Ok(SessionEvent::Greeting { payload, .. }) if greeting.is_none() => {
+                    greeting = Some(STANDARD.decode(payload).map_err(|e| e.to_string())?)
+                }
+                Ok(SessionEvent::Command { entry, .. }) => responses.record(entry)?,
+                Ok(_) => {}
+                Err(e) => error!("Skipping line {} of {}: {}", i + 1, path, e),
+            }
+        }
+
+        Ok(Session {
+            greeting: greeting.ok_or(format!("No greeting recorded in {}", path))?,
+            responses: Arc::new(responses),
+        })
+    }
+
+    /// Greeting for a new client, from the recorded one.
+    pub fn greeting(&self, connection: u64) -> Vec<u8> {
+        regenerate_greeting(&self.greeting, connection).unwrap_or_else(|| self.greeting.clone())
+    }
+}
+
+/// Copy of a Protocol::HandshakeV10 payload with the given connection id, a new scramble and without CLIENT_SSL, as
+/// the proxy can't negotiate TLS on behalf of a server that isn't there.
+fn regenerate_greeting(template: &[u8], connection: u64) -> Option<Vec<u8>> {
+    let mut greeting = template.to_vec();
+
+    let thread_id = 1 + greeting.get(1..)?.iter().position(|byte| *byte == 0)? + 1;
+    let scramble_1 = thread_id + 4;
+    let capability_flags_1 = scramble_1 + 8 + 1;
+    let auth_plugin_data_len = capability_flags_1 + 2 + 1 + 2 + 2;
+    let scramble_2 = auth_plugin_data_len + 1 + 10;
+
+    greeting
+        .get_mut(thread_id..scramble_1)?
+        .copy_from_slice(&IntFixedLen::encode(connection & 0xffffffff, Some(4)));
+
+    let flags = IntFixedLen::from_bytes(
+        &greeting
+            .get(capability_flags_1..capability_flags_1 + 2)?
+            .to_vec(),
+        Some(2),
+    )
+    .result
+        & !(CapabilityFlags::ClientSsl as u64);
+    greeting[capability_flags_1..capability_flags_1 + 2]
+        .copy_from_slice(&IntFixedLen::encode(flags, Some(2)));
+
+    // The second part of the scramble ends with a NUL byte.
+    let scramble_2_length = (*greeting.get(auth_plugin_data_len)? as usize)
+        .max(21)
+        .saturating_sub(8 + 1);
+    let mut scramble = scramble(8 + scramble_2_length).into_iter();
+    for i in (scramble_1..scramble_1 + 8).chain(scramble_2..scramble_2 + scramble_2_length) {
+        *greeting.get_mut(i)? = scramble.next()?;
+    }
+
+    Some(greeting)
+}
+
+/// Random printable bytes.
+fn scramble(length: usize) -> Vec<u8> {
+    std::iter::repeat_with(|| Uuid::new_v4().into_bytes())
+        .flatten()
+        .map(|byte| b'!' + byte % (b'~' - b'!'))
+        .take(length)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::session::regenerate_greeting;
+
+    #[test]
+    fn test_regenerate_greeting() {
+        let template: Vec<u8> = vec![
+            0x0a, 0x38, 0x2e, 0x30, 0x2e, 0x33, 0x32, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x15, 0x51,
+            0x79, 0x32, 0x2c, 0x6e, 0x09, 0x77, 0x00, 0xff, 0xff, 0xff, 0x02, 0x00, 0xff, 0xdf,
+            0x15, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x43, 0x28, 0x36,
+            0x51, 0x2c, 0x51, 0x74, 0x7c, 0x62, 0x08, 0x60, 0x22, 0x00, 0x63, 0x61, 0x63, 0x68,
+            0x69, 0x6e, 0x67, 0x5f, 0x73, 0x68, 0x61, 0x32, 0x5f, 0x70, 0x61, 0x73, 0x73, 0x77,
+            0x6f, 0x72, 0x64, 0x00,
+        ];
+
+        let greeting = regenerate_greeting(&template, 0x0102).unwrap();
+
+        assert_eq!(template.len(), greeting.len());
+        // Server version
+        assert_eq!(template[..8], greeting[..8]);
+        assert_eq!(vec![0x02, 0x01, 0x00, 0x00], greeting[8..12]);
+        assert_ne!(template[12..20], greeting[12..20]);
+        // CLIENT_SSL is cleared from the lower capability flags.
+        assert_eq!(vec![0xff, 0xf7], greeting[21..23]);
+        assert_eq!(template[23..39], greeting[23..39]);
+        assert_ne!(template[39..51], greeting[39..51]);
+        assert!(greeting[39..51].iter().all(|byte| byte.is_ascii_graphic()));
+        // Trailing NUL of the scramble and auth plugin name
+        assert_eq!(template[51..], greeting[51..]);
+    }
+}
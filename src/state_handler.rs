@@ -1,3 +1,4 @@
+use crate::materialization::namespace;
 use crate::mysql::accumulator::auth_complete::AuthCompleteAccumulator;
 use crate::mysql::accumulator::auth_init::AuthInitAccumulator;
 use crate::mysql::accumulator::auth_switch_response::AuthSwitchResponseAccumulator;
@@ -7,6 +8,7 @@ use crate::mysql::accumulator::Accumulator;
 use crate::mysql::accumulator::{
     handshake::HandshakeAccumulator, handshake_response::HandshakeResponseAccumulator,
 };
+use crate::stats::{self, STATS};
 use crate::util::packet_printer;
 use crate::{
     connection::{Connection, Phase},
@@ -37,6 +39,7 @@ pub fn process_incoming_frame(
         packet_printer::print_packet(&packet);
 
         connection.phase = accumulator.consume(&mut packet, connection);
+        out_packets.extend(accumulator.take_injected_packets());
         if !packet.skip {
             out_packets.push(packet);
         }
@@ -57,13 +60,36 @@ fn sync_connection_state(connection: &mut Connection, accumulator: Box<dyn Accum
             connection.handshake = delta.handshake
         }
         if delta.handshake_response.is_some() {
+            connection.current_database = delta
+                .handshake_response
+                .as_ref()
+                .and_then(|response| response.database().map(str::to_string));
+            connection.namespace = delta
+                .handshake_response
+                .as_ref()
+                .map(namespace::connection_namespace)
+                .unwrap_or_default();
             connection.handshake_response = delta.handshake_response
         }
         if delta.last_command.is_some() {
-            connection.last_command = delta.last_command
+            stats::increment(&STATS.commands);
+            connection.last_command = delta.last_command;
+            namespace::select_namespace(connection);
         }
-        if delta.response.is_some() {
-            connection.set_response_accumulator(delta.response.unwrap());
+        if let Some(response) = delta.response {
+            if response.succeeded() {
+                if let Some(database) = connection
+                    .get_last_command()
+                    .and_then(|command| command.selected_database())
+                {
+                    connection.current_database = Some(database);
+                }
+
+                if let Some(command) = &connection.last_command {
+                    connection.transaction.track(command);
+                }
+            }
+            connection.set_response_accumulator(response);
         }
     }
 }
@@ -154,7 +180,7 @@ fn parse_buffer(buf: &[u8], start_offset: &mut usize, phase: Phase) -> PacketPar
     PacketParseResult::Packet(packet)
 }
 
-pub fn generate_outgoing_frame(packet: &Vec<Packet>) -> Vec<u8> {
+pub fn generate_outgoing_frame(packet: &[Packet]) -> Vec<u8> {
     let mut ret: Vec<u8> = Vec::new();
 
     for packet in packet.iter() {
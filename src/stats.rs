@@ -0,0 +1,23 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters of the proxy since startup, reported by the control API.
+pub struct Stats {
+    pub connections: AtomicU64,
+    pub commands: AtomicU64,
+    pub intercepted_writes: AtomicU64,
+    pub admin_commands: AtomicU64,
+    /// Commands without a recorded response while replaying.
+    pub replay_misses: AtomicU64,
+}
+
+pub static STATS: Stats = Stats {
+    connections: AtomicU64::new(0),
+    commands: AtomicU64::new(0),
+    intercepted_writes: AtomicU64::new(0),
+    admin_commands: AtomicU64::new(0),
+    replay_misses: AtomicU64::new(0),
+};
+
+pub fn increment(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}